@@ -0,0 +1,29 @@
+//! A compact CBOR representation of [`AlfalfaContents`].
+//!
+//! This is a direct serialization of [`AlfalfaContents`], so binary and image entries are
+//! stored as CBOR byte strings rather than base64 text.
+
+use crate::{errors::*, model::*};
+
+pub fn alfalfa_contents_to_cbor(contents: &AlfalfaContents) -> Result<Vec<u8>> {
+    #[derive(serde::Serialize)]
+    struct SortedContents<'a> {
+        version: u8,
+        data: std::collections::BTreeMap<&'a String, &'a AlfalfaEntryData>,
+    }
+
+    let sorted = SortedContents {
+        version: contents.version,
+        data: contents.data.iter().collect(),
+    };
+
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&sorted, &mut bytes)
+        .map_err(|err| AlfalfaInspectorError::CborError(err.to_string()))?;
+
+    Ok(bytes)
+}
+
+pub fn alfalfa_contents_from_cbor(bytes: &[u8]) -> Result<AlfalfaContents> {
+    ciborium::from_reader(bytes).map_err(|err| AlfalfaInspectorError::CborError(err.to_string()))
+}
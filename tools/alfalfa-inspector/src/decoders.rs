@@ -0,0 +1,344 @@
+//! Structured views of binary alfalfa entries.
+//!
+//! Entries whose key has a registered [`AlfalfaEntryDecoder`] are broken down into named fields.
+//! Every entry also gets a hexdump and an entropy estimate, so that unknown data can still be
+//! looked at. Decoders can be written in Rust, or described as a [`LayoutDecoder`] (which is what
+//! JavaScript callers register).
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::*;
+
+/// Number of bytes shown on each hexdump line.
+const HEXDUMP_WIDTH: usize = 16;
+
+/// A single value read out of a binary entry.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedField {
+    pub name: String,
+    pub offset: usize,
+    pub length: usize,
+    pub value: String,
+}
+
+/// Knows how to read the data stored under some alfalfa key.
+pub trait AlfalfaEntryDecoder {
+    /// A human readable name for the format.
+    fn name(&self) -> &str;
+
+    fn handles(&self, key: &str) -> bool;
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<DecodedField>, String>;
+}
+
+#[derive(Default)]
+pub struct DecoderRegistry {
+    decoders: Vec<Box<dyn AlfalfaEntryDecoder>>,
+}
+
+impl DecoderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a decoder. Later decoders take precedence over earlier ones for the same key.
+    pub fn register(&mut self, decoder: Box<dyn AlfalfaEntryDecoder>) {
+        self.decoders.push(decoder);
+    }
+
+    pub fn clear(&mut self) {
+        self.decoders.clear();
+    }
+
+    pub fn find(&self, key: &str) -> Option<&dyn AlfalfaEntryDecoder> {
+        self.decoders
+            .iter()
+            .rev()
+            .find(|decoder| decoder.handles(key))
+            .map(|decoder| decoder.as_ref())
+    }
+
+    pub fn inspect(&self, key: &str, data: &[u8]) -> BinaryEntryInspection {
+        let decoder = self.find(key);
+        let decoded = decoder.map(|decoder| decoder.decode(data));
+
+        BinaryEntryInspection {
+            key: key.to_owned(),
+            size: data.len(),
+            entropy: entropy(data),
+            decoder: decoder.map(|decoder| decoder.name().to_owned()),
+            fields: decoded.as_ref().and_then(|d| d.as_ref().ok().cloned()),
+            decode_error: decoded.and_then(|d| d.err()),
+            hexdump: hexdump(data),
+        }
+    }
+
+    /// Inspects every binary entry of `contents`, sorted by key.
+    pub fn inspect_binary_entries(&self, contents: &AlfalfaContents) -> Vec<BinaryEntryInspection> {
+        let mut inspections: Vec<_> = contents
+            .data
+            .iter()
+            .filter_map(|(key, entry)| match entry {
+                AlfalfaEntryData::Binary(data) => Some(self.inspect(key, data)),
+                _ => None,
+            })
+            .collect();
+
+        inspections.sort_by(|a, b| a.key.cmp(&b.key));
+
+        inspections
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinaryEntryInspection {
+    pub key: String,
+    pub size: usize,
+    /// Shannon entropy in bits per byte, from 0 (constant) to 8 (random or compressed).
+    pub entropy: f64,
+    /// The name of the decoder used, if one handles this key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoder: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<DecodedField>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decode_error: Option<String>,
+    pub hexdump: Vec<HexdumpLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HexdumpLine {
+    pub offset: usize,
+    pub hex: String,
+    /// The bytes as ASCII, with anything unprintable shown as `.`.
+    pub ascii: String,
+}
+
+pub fn hexdump(data: &[u8]) -> Vec<HexdumpLine> {
+    data.chunks(HEXDUMP_WIDTH)
+        .enumerate()
+        .map(|(line, bytes)| {
+            let hex = bytes
+                .iter()
+                .enumerate()
+                .map(|(index, byte)| {
+                    let separator = if index == HEXDUMP_WIDTH / 2 {
+                        "  "
+                    } else {
+                        " "
+                    };
+                    let separator = if index == 0 { "" } else { separator };
+
+                    format!("{separator}{byte:02x}")
+                })
+                .collect();
+
+            let ascii = bytes
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+
+            HexdumpLine {
+                offset: line * HEXDUMP_WIDTH,
+                hex,
+                ascii,
+            }
+        })
+        .collect()
+}
+
+pub fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / data.len() as f64;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum LayoutFieldType {
+    U8,
+    I8,
+    Bool,
+    U16Le,
+    U16Be,
+    I16Le,
+    I16Be,
+    U32Le,
+    U32Be,
+    I32Le,
+    I32Be,
+    F32Le,
+    F32Be,
+    /// ASCII text of a fixed length, or up to the end of the data when no length is given.
+    Ascii {
+        length: Option<usize>,
+    },
+    /// Raw bytes of a fixed length, or up to the end of the data when no length is given.
+    Bytes {
+        length: Option<usize>,
+    },
+}
+
+impl LayoutFieldType {
+    fn length(self, remaining: usize) -> usize {
+        match self {
+            Self::U8 | Self::I8 | Self::Bool => 1,
+            Self::U16Le | Self::U16Be | Self::I16Le | Self::I16Be => 2,
+            Self::U32Le | Self::U32Be | Self::I32Le | Self::I32Be | Self::F32Le | Self::F32Be => 4,
+            Self::Ascii { length } | Self::Bytes { length } => length.unwrap_or(remaining),
+        }
+    }
+
+    fn format(self, bytes: &[u8]) -> String {
+        let two = || [bytes[0], bytes[1]];
+        let four = || [bytes[0], bytes[1], bytes[2], bytes[3]];
+
+        match self {
+            Self::U8 => bytes[0].to_string(),
+            Self::I8 => (bytes[0] as i8).to_string(),
+            Self::Bool => (bytes[0] != 0).to_string(),
+            Self::U16Le => u16::from_le_bytes(two()).to_string(),
+            Self::U16Be => u16::from_be_bytes(two()).to_string(),
+            Self::I16Le => i16::from_le_bytes(two()).to_string(),
+            Self::I16Be => i16::from_be_bytes(two()).to_string(),
+            Self::U32Le => u32::from_le_bytes(four()).to_string(),
+            Self::U32Be => u32::from_be_bytes(four()).to_string(),
+            Self::I32Le => i32::from_le_bytes(four()).to_string(),
+            Self::I32Be => i32::from_be_bytes(four()).to_string(),
+            Self::F32Le => f32::from_le_bytes(four()).to_string(),
+            Self::F32Be => f32::from_be_bytes(four()).to_string(),
+            Self::Ascii { .. } => bytes
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii() {
+                        byte as char
+                    } else {
+                        '\u{FFFD}'
+                    }
+                })
+                .collect(),
+            Self::Bytes { .. } => bytes
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutField {
+    pub name: String,
+    #[serde(flatten)]
+    pub field_type: LayoutFieldType,
+}
+
+/// A decoder for a fixed sequence of fields, described as data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutDecoder {
+    pub key: String,
+    pub name: String,
+    pub fields: Vec<LayoutField>,
+}
+
+impl AlfalfaEntryDecoder for LayoutDecoder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn handles(&self, key: &str) -> bool {
+        self.key == key
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<DecodedField>, String> {
+        let mut fields = Vec::with_capacity(self.fields.len());
+        let mut offset = 0;
+
+        for field in &self.fields {
+            let length = field.field_type.length(data.len() - offset);
+
+            let Some(bytes) = offset
+                .checked_add(length)
+                .and_then(|end| data.get(offset..end))
+            else {
+                return Err(format!(
+                    "Field {:?} needs {length} bytes at offset {offset}, but the entry is only {} bytes long",
+                    field.name,
+                    data.len()
+                ));
+            };
+
+            fields.push(DecodedField {
+                name: field.name.clone(),
+                offset,
+                length,
+                value: field.field_type.format(bytes),
+            });
+
+            offset += length;
+        }
+
+        if offset < data.len() {
+            return Err(format!(
+                "{} bytes are left over after the last field",
+                data.len() - offset
+            ));
+        }
+
+        Ok(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_field_lengths_are_rejected() {
+        let decoder = LayoutDecoder {
+            key: "test".to_owned(),
+            name: "Test".to_owned(),
+            fields: vec![
+                LayoutField {
+                    name: "flag".to_owned(),
+                    field_type: LayoutFieldType::U8,
+                },
+                LayoutField {
+                    name: "payload".to_owned(),
+                    field_type: LayoutFieldType::Bytes {
+                        length: Some(usize::MAX),
+                    },
+                },
+            ],
+        };
+
+        assert_eq!(
+            decoder.decode(&[1, 2, 3]),
+            Err(format!(
+                "Field \"payload\" needs {} bytes at offset 1, but the entry is only 3 bytes long",
+                usize::MAX
+            ))
+        );
+    }
+}
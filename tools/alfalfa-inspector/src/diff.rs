@@ -0,0 +1,447 @@
+use ears_rs::alfalfa::{
+    utils::{EraseRegion, EraseRegionsProvider},
+    AlfalfaData, AlfalfaDataKey,
+};
+use serde::Serialize;
+
+use crate::{errors::*, model::*};
+
+/// The differences between the alfalfa data of two skins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlfalfaDiff {
+    pub version_before: u8,
+    pub version_after: u8,
+    pub added: Vec<AlfalfaEntrySummary>,
+    pub removed: Vec<AlfalfaEntrySummary>,
+    pub modified: Vec<AlfalfaEntryChange>,
+    /// Keys whose entries are identical in both skins.
+    pub unchanged: Vec<String>,
+}
+
+impl AlfalfaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.version_before == self.version_after
+            && self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlfalfaEntrySummary {
+    pub key: String,
+    pub entry_type: &'static str,
+    /// Size of the entry as stored in the skin.
+    pub byte_size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlfalfaEntryChange {
+    pub key: String,
+    pub type_before: &'static str,
+    pub type_after: &'static str,
+    pub size_before: usize,
+    pub size_after: usize,
+    pub size_delta: i64,
+    /// A closer look at what changed, when both entries are of a type that can be compared.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<AlfalfaEntryChangeDetails>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum AlfalfaEntryChangeDetails {
+    #[serde(rename_all = "camelCase")]
+    Erase {
+        added_regions: Vec<AlfalfaEraseEntryData>,
+        removed_regions: Vec<AlfalfaEraseEntryData>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Image {
+        /// `None` when the entry could not be decoded.
+        dimensions_before: Option<ImageDimensions>,
+        dimensions_after: Option<ImageDimensions>,
+        /// Number of pixels that differ. Only counted when both images decode and share a size.
+        changed_pixels: Option<usize>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ImageDimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl From<&image::RgbaImage> for ImageDimensions {
+    fn from(image: &image::RgbaImage) -> Self {
+        Self {
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+}
+
+fn entry_type(entry: &AlfalfaEntryData) -> &'static str {
+    match entry {
+        AlfalfaEntryData::Binary(_) => "binary",
+        AlfalfaEntryData::Image(_) => "image",
+        AlfalfaEntryData::Erase(_) => "erase",
+    }
+}
+
+/// Size of the entry once it is encoded into the skin.
+fn entry_byte_size(entry: &AlfalfaEntryData) -> Result<usize> {
+    match entry {
+        AlfalfaEntryData::Binary(data) | AlfalfaEntryData::Image(data) => Ok(data.len()),
+        AlfalfaEntryData::Erase(regions) => {
+            let regions: Vec<_> = regions
+                .iter()
+                .map(|region| EraseRegion {
+                    x: region.x,
+                    y: region.y,
+                    width: region.width,
+                    height: region.height,
+                })
+                .collect();
+
+            let mut alfalfa = AlfalfaData::new();
+            alfalfa.set_erase_regions(&regions)?;
+
+            Ok(alfalfa
+                .get_data(AlfalfaDataKey::Erase)
+                .map_or(0, <[u8]>::len))
+        }
+    }
+}
+
+fn summarize(key: &str, entry: &AlfalfaEntryData) -> Result<AlfalfaEntrySummary> {
+    Ok(AlfalfaEntrySummary {
+        key: key.to_owned(),
+        entry_type: entry_type(entry),
+        byte_size: entry_byte_size(entry)?,
+    })
+}
+
+/// Regions of `regions` that have no counterpart in `other`, counting duplicates separately.
+fn regions_missing_from(
+    regions: &[AlfalfaEraseEntryData],
+    other: &[AlfalfaEraseEntryData],
+) -> Vec<AlfalfaEraseEntryData> {
+    let mut unmatched = other.to_vec();
+
+    regions
+        .iter()
+        .filter(|region| match unmatched.iter().position(|r| r == *region) {
+            Some(index) => {
+                unmatched.swap_remove(index);
+                false
+            }
+            None => true,
+        })
+        .copied()
+        .collect()
+}
+
+fn compare_images(before: &[u8], after: &[u8]) -> AlfalfaEntryChangeDetails {
+    let before = image::load_from_memory(before).ok().map(|i| i.into_rgba8());
+    let after = image::load_from_memory(after).ok().map(|i| i.into_rgba8());
+
+    let changed_pixels = match (&before, &after) {
+        (Some(before), Some(after)) if before.dimensions() == after.dimensions() => Some(
+            before
+                .pixels()
+                .zip(after.pixels())
+                .filter(|(a, b)| a != b)
+                .count(),
+        ),
+        _ => None,
+    };
+
+    AlfalfaEntryChangeDetails::Image {
+        dimensions_before: before.as_ref().map(ImageDimensions::from),
+        dimensions_after: after.as_ref().map(ImageDimensions::from),
+        changed_pixels,
+    }
+}
+
+fn compare_entries(
+    key: &str,
+    before: &AlfalfaEntryData,
+    after: &AlfalfaEntryData,
+) -> Result<AlfalfaEntryChange> {
+    let details = match (before, after) {
+        (AlfalfaEntryData::Erase(before), AlfalfaEntryData::Erase(after)) => {
+            Some(AlfalfaEntryChangeDetails::Erase {
+                added_regions: regions_missing_from(after, before),
+                removed_regions: regions_missing_from(before, after),
+            })
+        }
+        (AlfalfaEntryData::Image(before), AlfalfaEntryData::Image(after)) => {
+            Some(compare_images(before, after))
+        }
+        _ => None,
+    };
+
+    let size_before = entry_byte_size(before)?;
+    let size_after = entry_byte_size(after)?;
+
+    Ok(AlfalfaEntryChange {
+        key: key.to_owned(),
+        type_before: entry_type(before),
+        type_after: entry_type(after),
+        size_before,
+        size_after,
+        size_delta: size_after as i64 - size_before as i64,
+        details,
+    })
+}
+
+/// Compares the alfalfa data of two skins. Every list in the result is sorted by key.
+pub fn diff_alfalfa_contents(
+    before: &AlfalfaContents,
+    after: &AlfalfaContents,
+) -> Result<AlfalfaDiff> {
+    let mut diff = AlfalfaDiff {
+        version_before: before.version,
+        version_after: after.version,
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+        unchanged: Vec::new(),
+    };
+
+    for (key, before_entry) in &before.data {
+        match after.data.get(key) {
+            None => diff.removed.push(summarize(key, before_entry)?),
+            Some(after_entry) if after_entry == before_entry => diff.unchanged.push(key.clone()),
+            Some(after_entry) => {
+                diff.modified
+                    .push(compare_entries(key, before_entry, after_entry)?)
+            }
+        }
+    }
+
+    for (key, after_entry) in &after.data {
+        if !before.data.contains_key(key) {
+            diff.added.push(summarize(key, after_entry)?);
+        }
+    }
+
+    diff.added.sort_by(|a, b| a.key.cmp(&b.key));
+    diff.removed.sort_by(|a, b| a.key.cmp(&b.key));
+    diff.modified.sort_by(|a, b| a.key.cmp(&b.key));
+    diff.unchanged.sort();
+
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn contents(entries: &[(&str, AlfalfaEntryData)]) -> AlfalfaContents {
+        AlfalfaContents {
+            data: entries
+                .iter()
+                .map(|(key, entry)| ((*key).to_owned(), entry.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn region(x: u8, y: u8) -> AlfalfaEraseEntryData {
+        AlfalfaEraseEntryData {
+            x,
+            y,
+            width: 4,
+            height: 4,
+        }
+    }
+
+    fn png(image: &RgbaImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+
+        bytes
+    }
+
+    fn keys(summaries: &[AlfalfaEntrySummary]) -> Vec<&str> {
+        summaries
+            .iter()
+            .map(|summary| summary.key.as_str())
+            .collect()
+    }
+
+    fn modified_entry(before: AlfalfaEntryData, after: AlfalfaEntryData) -> AlfalfaEntryChange {
+        let diff = diff_alfalfa_contents(
+            &contents(&[("entry", before)]),
+            &contents(&[("entry", after)]),
+        )
+        .unwrap();
+
+        assert_eq!(diff.modified.len(), 1);
+        diff.modified.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn identical_contents_have_no_differences() {
+        let contents = contents(&[
+            ("b", AlfalfaEntryData::Binary(vec![1])),
+            ("a", AlfalfaEntryData::Erase(vec![region(0, 0)])),
+        ]);
+
+        let diff = diff_alfalfa_contents(&contents, &contents.clone()).unwrap();
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.unchanged, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn version_changes_are_differences() {
+        let before = contents(&[]);
+        let after = AlfalfaContents {
+            version: before.version + 1,
+            ..before.clone()
+        };
+
+        assert!(!diff_alfalfa_contents(&before, &after).unwrap().is_empty());
+    }
+
+    #[test]
+    fn entries_are_sorted_into_added_removed_and_unchanged() {
+        let before = contents(&[
+            ("kept", AlfalfaEntryData::Binary(vec![1])),
+            ("gone", AlfalfaEntryData::Binary(vec![1, 2, 3])),
+            ("also gone", AlfalfaEntryData::Image(vec![1, 2])),
+        ]);
+        let after = contents(&[
+            ("kept", AlfalfaEntryData::Binary(vec![1])),
+            ("new", AlfalfaEntryData::Binary(vec![1, 2, 3, 4])),
+        ]);
+
+        let diff = diff_alfalfa_contents(&before, &after).unwrap();
+
+        assert_eq!(keys(&diff.removed), vec!["also gone", "gone"]);
+        assert_eq!(diff.removed[0].entry_type, "image");
+        assert_eq!(diff.removed[1].byte_size, 3);
+        assert_eq!(keys(&diff.added), vec!["new"]);
+        assert_eq!(diff.added[0].byte_size, 4);
+        assert_eq!(diff.unchanged, vec!["kept"]);
+        assert!(diff.modified.is_empty());
+    }
+
+    #[test]
+    fn modified_entries_report_their_size_change() {
+        let change = modified_entry(
+            AlfalfaEntryData::Binary(vec![0; 10]),
+            AlfalfaEntryData::Binary(vec![0; 4]),
+        );
+
+        assert_eq!((change.size_before, change.size_after), (10, 4));
+        assert_eq!(change.size_delta, -6);
+        assert_eq!(change.details, None);
+    }
+
+    #[test]
+    fn entries_that_change_type_are_not_compared_further() {
+        let change = modified_entry(
+            AlfalfaEntryData::Binary(vec![1, 2]),
+            AlfalfaEntryData::Image(vec![1, 2]),
+        );
+
+        assert_eq!((change.type_before, change.type_after), ("binary", "image"));
+        assert_eq!(change.details, None);
+    }
+
+    #[test]
+    fn erase_regions_are_matched_one_to_one() {
+        let change = modified_entry(
+            AlfalfaEntryData::Erase(vec![region(0, 0), region(0, 0), region(8, 8)]),
+            AlfalfaEntryData::Erase(vec![region(0, 0), region(16, 16)]),
+        );
+
+        assert_eq!(
+            change.details,
+            Some(AlfalfaEntryChangeDetails::Erase {
+                added_regions: vec![region(16, 16)],
+                removed_regions: vec![region(0, 0), region(8, 8)],
+            })
+        );
+    }
+
+    #[test]
+    fn changed_pixels_are_counted_for_images_of_the_same_size() {
+        let before = RgbaImage::from_pixel(20, 16, Rgba([0xFF; 4]));
+        let mut after = before.clone();
+        after.put_pixel(0, 0, Rgba([0, 0, 0, 0xFF]));
+        after.put_pixel(19, 15, Rgba([0, 0, 0, 0]));
+
+        let change = modified_entry(
+            AlfalfaEntryData::Image(png(&before)),
+            AlfalfaEntryData::Image(png(&after)),
+        );
+
+        let dimensions = Some(ImageDimensions {
+            width: 20,
+            height: 16,
+        });
+        assert_eq!(
+            change.details,
+            Some(AlfalfaEntryChangeDetails::Image {
+                dimensions_before: dimensions,
+                dimensions_after: dimensions,
+                changed_pixels: Some(2),
+            })
+        );
+    }
+
+    #[test]
+    fn pixels_are_not_counted_for_resized_or_broken_images() {
+        let image = RgbaImage::new(20, 16);
+
+        let resized = modified_entry(
+            AlfalfaEntryData::Image(png(&image)),
+            AlfalfaEntryData::Image(png(&RgbaImage::new(10, 8))),
+        );
+        let Some(AlfalfaEntryChangeDetails::Image {
+            dimensions_after,
+            changed_pixels,
+            ..
+        }) = resized.details
+        else {
+            panic!("images should be compared as images");
+        };
+        assert_eq!(
+            dimensions_after,
+            Some(ImageDimensions {
+                width: 10,
+                height: 8
+            })
+        );
+        assert_eq!(changed_pixels, None);
+
+        let broken = modified_entry(
+            AlfalfaEntryData::Image(png(&image)),
+            AlfalfaEntryData::Image(vec![1, 2, 3]),
+        );
+        let Some(AlfalfaEntryChangeDetails::Image {
+            dimensions_after,
+            changed_pixels,
+            ..
+        }) = broken.details
+        else {
+            panic!("images should be compared as images");
+        };
+        assert_eq!(dimensions_after, None);
+        assert_eq!(changed_pixels, None);
+    }
+}
@@ -0,0 +1,38 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AlfalfaInspectorError {
+    #[error("Ears error: {0}")]
+    EarsError(#[from] ears_rs::utils::errors::EarsError),
+
+    #[error("Image error: {0}")]
+    ImageError(#[from] image::error::ImageError),
+
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("Invalid base64 data in entry {key:?}: {source}")]
+    Base64Error {
+        key: String,
+        #[source]
+        source: base64::DecodeError,
+    },
+
+    #[error("Unsupported alfalfa version {version} (only version {supported} is supported)")]
+    UnsupportedVersion { version: u8, supported: u8 },
+
+    #[error("There is no image entry named {key:?}")]
+    NotAnImageEntry { key: String },
+
+    #[error("Invalid preview scale {scale} (must be between 1 and 32)")]
+    InvalidScale { scale: u32 },
+
+    #[error("The alfalfa data does not fit in this skin ({overflow} bytes too many)")]
+    AlfalfaTooLarge { overflow: usize },
+
+    #[cfg(feature = "cbor")]
+    #[error("CBOR error: {0}")]
+    CborError(String),
+}
+
+pub type Result<T> = std::result::Result<T, AlfalfaInspectorError>;
@@ -0,0 +1,274 @@
+use std::io::Cursor;
+
+use ears_rs::alfalfa::AlfalfaDataKey;
+use image::{imageops::FilterType, ImageFormat};
+use serde::Serialize;
+use skin_utils::optimize::{self, ImageOptimizationReport};
+
+use crate::{errors::*, logic::known_alfalfa_key, model::*};
+
+/// Largest factor a preview can be scaled up by.
+pub const MAX_PREVIEW_SCALE: u32 = 32;
+
+/// What a decoded image entry looks like, and whether the Ears mod will accept it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageEntryInfo {
+    pub key: String,
+    pub byte_size: usize,
+    /// Whether the entry could be decoded as an image at all.
+    pub valid: bool,
+    /// Why decoding failed, when it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_height: Option<u32>,
+    /// Whether the image has the size Ears expects for this key. Always true for keys Ears has no
+    /// expectations for.
+    pub has_expected_size: bool,
+}
+
+/// The texture size the Ears mod expects for an image stored under `key`.
+pub fn expected_image_size(key: &str) -> Option<(u32, u32)> {
+    match known_alfalfa_key(key)? {
+        AlfalfaDataKey::Cape | AlfalfaDataKey::Wings => Some((20, 16)),
+        _ => None,
+    }
+}
+
+/// Decodes an image entry and checks it against what Ears expects. Decoding failures are reported
+/// in the result rather than as an error, as finding them is the point of inspecting.
+pub fn inspect_image_entry(key: &str, data: &[u8]) -> ImageEntryInfo {
+    let expected = expected_image_size(key);
+
+    let mut info = ImageEntryInfo {
+        key: key.to_owned(),
+        byte_size: data.len(),
+        valid: false,
+        error: None,
+        width: None,
+        height: None,
+        color_type: None,
+        expected_width: expected.map(|(width, _)| width),
+        expected_height: expected.map(|(_, height)| height),
+        has_expected_size: false,
+    };
+
+    match image::load_from_memory(data) {
+        Ok(image) => {
+            info.valid = true;
+            info.width = Some(image.width());
+            info.height = Some(image.height());
+            info.color_type = Some(format!("{:?}", image.color()));
+            info.has_expected_size =
+                expected.is_none_or(|size| size == (image.width(), image.height()));
+        }
+        Err(err) => info.error = Some(err.to_string()),
+    }
+
+    info
+}
+
+/// Inspects every image entry of `contents`, sorted by key.
+pub fn inspect_image_entries(contents: &AlfalfaContents) -> Vec<ImageEntryInfo> {
+    let mut infos: Vec<_> = contents
+        .data
+        .iter()
+        .filter_map(|(key, entry)| match entry {
+            AlfalfaEntryData::Image(data) => Some(inspect_image_entry(key, data)),
+            _ => None,
+        })
+        .collect();
+
+    infos.sort_by(|a, b| a.key.cmp(&b.key));
+
+    infos
+}
+
+/// Renders the image entry stored under `key` as a PNG, scaled up by `scale` without smoothing.
+pub fn render_image_entry_preview(
+    contents: &AlfalfaContents,
+    key: &str,
+    scale: u32,
+) -> Result<Vec<u8>> {
+    let Some(AlfalfaEntryData::Image(data)) = contents.data.get(key) else {
+        return Err(AlfalfaInspectorError::NotAnImageEntry {
+            key: key.to_owned(),
+        });
+    };
+
+    if !(1..=MAX_PREVIEW_SCALE).contains(&scale) {
+        return Err(AlfalfaInspectorError::InvalidScale { scale });
+    }
+
+    let image = image::load_from_memory(data)?.into_rgba8();
+    let preview = image::imageops::resize(
+        &image,
+        image.width() * scale,
+        image.height() * scale,
+        FilterType::Nearest,
+    );
+
+    let mut bytes = Vec::new();
+    preview.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+
+    Ok(bytes)
+}
+
+/// Re-encodes every image entry with [`optimize::optimize_image_entries`]. Entries that don't
+/// decode are left as-is.
+pub fn optimize_image_entries(contents: &mut AlfalfaContents) -> ImageOptimizationReport {
+    let images = contents
+        .data
+        .iter_mut()
+        .filter_map(|(key, entry)| match entry {
+            AlfalfaEntryData::Image(data) => Some((key.as_str(), data)),
+            _ => None,
+        });
+
+    optimize::optimize_image_entries(images)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([x as u8 * 10, y as u8 * 10, 0x80, 0xFF])
+        });
+
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+
+        bytes
+    }
+
+    fn sample_contents() -> AlfalfaContents {
+        AlfalfaContents {
+            data: AlfalfaDataMap::from([
+                ("wings".to_owned(), AlfalfaEntryData::Image(png(20, 16))),
+                ("cape".to_owned(), AlfalfaEntryData::Image(png(10, 16))),
+                ("broken".to_owned(), AlfalfaEntryData::Image(vec![1, 2, 3])),
+                ("custom".to_owned(), AlfalfaEntryData::Binary(vec![4, 5])),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_capes_and_wings_have_an_expected_size() {
+        assert_eq!(expected_image_size("cape"), Some((20, 16)));
+        assert_eq!(expected_image_size("wings"), Some((20, 16)));
+        assert_eq!(expected_image_size("erase"), None);
+        assert_eq!(expected_image_size("custom"), None);
+    }
+
+    #[test]
+    fn image_entries_are_checked_against_the_expected_size() {
+        let wings = inspect_image_entry("wings", &png(20, 16));
+        assert!(wings.valid);
+        assert_eq!((wings.width, wings.height), (Some(20), Some(16)));
+        assert_eq!(wings.color_type.as_deref(), Some("Rgba8"));
+        assert!(wings.has_expected_size);
+
+        let cape = inspect_image_entry("cape", &png(10, 16));
+        assert!(cape.valid);
+        assert_eq!(
+            (cape.expected_width, cape.expected_height),
+            (Some(20), Some(16))
+        );
+        assert!(!cape.has_expected_size);
+
+        let custom = inspect_image_entry("custom", &png(3, 3));
+        assert_eq!(custom.expected_width, None);
+        assert!(custom.has_expected_size);
+    }
+
+    #[test]
+    fn undecodable_entries_are_reported_rather_than_refused() {
+        let info = inspect_image_entry("cape", &[1, 2, 3]);
+
+        assert!(!info.valid);
+        assert!(info.error.is_some());
+        assert_eq!(info.byte_size, 3);
+        assert_eq!(info.width, None);
+        assert!(!info.has_expected_size);
+    }
+
+    #[test]
+    fn only_image_entries_are_inspected_in_key_order() {
+        let keys: Vec<_> = inspect_image_entries(&sample_contents())
+            .into_iter()
+            .map(|info| info.key)
+            .collect();
+
+        assert_eq!(keys, vec!["broken", "cape", "wings"]);
+    }
+
+    #[test]
+    fn previews_are_scaled_without_smoothing() {
+        let preview = render_image_entry_preview(&sample_contents(), "wings", 3).unwrap();
+        let preview = image::load_from_memory(&preview).unwrap().into_rgba8();
+
+        assert_eq!(preview.dimensions(), (60, 48));
+        assert_eq!(preview.get_pixel(5, 2), &Rgba([10, 0, 0x80, 0xFF]));
+    }
+
+    #[test]
+    fn previews_need_an_image_entry_and_a_valid_scale() {
+        let contents = sample_contents();
+
+        for key in ["custom", "missing"] {
+            assert!(matches!(
+                render_image_entry_preview(&contents, key, 1),
+                Err(AlfalfaInspectorError::NotAnImageEntry { .. })
+            ));
+        }
+
+        for scale in [0, MAX_PREVIEW_SCALE + 1] {
+            assert!(matches!(
+                render_image_entry_preview(&contents, "wings", scale),
+                Err(AlfalfaInspectorError::InvalidScale { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn only_decodable_image_entries_are_optimized() {
+        let mut contents = sample_contents();
+        let report = optimize_image_entries(&mut contents);
+
+        let keys: Vec<_> = report
+            .images
+            .iter()
+            .map(|image| image.key.as_str())
+            .collect();
+        assert_eq!(keys, vec!["cape", "wings"]);
+
+        let original = sample_contents();
+        for key in ["broken", "custom"] {
+            assert_eq!(contents.data[key], original.data[key]);
+        }
+
+        let Some(AlfalfaEntryData::Image(wings)) = contents.data.get("wings") else {
+            panic!("wings should still be an image");
+        };
+        assert_eq!(
+            image::load_from_memory(wings).unwrap().into_rgba8(),
+            image::load_from_memory(&png(20, 16)).unwrap().into_rgba8()
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! A stable, human readable JSON representation of [`AlfalfaContents`].
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "data": {
+//!     "cape": { "type": "image", "value": "iVBORw0KGgo..." },
+//!     "erase": { "type": "erase", "value": [{ "x": 0, "y": 0, "width": 8, "height": 8 }] }
+//!   }
+//! }
+//! ```
+//!
+//! Binary and image entries are base64 encoded (standard alphabet, with padding), and entries are
+//! always written in key order so that the output diffs cleanly. The entries are stored under
+//! `data`, as in [`AlfalfaContents`]; documents that use the older `entries` name are still read.
+
+use std::collections::BTreeMap;
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::*, model::*};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+enum JsonAlfalfaEntry {
+    #[serde(rename = "binary")]
+    Binary(String),
+    #[serde(rename = "image")]
+    Image(String),
+    #[serde(rename = "erase")]
+    Erase(Vec<AlfalfaEraseEntryData>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct JsonAlfalfaDocument {
+    version: u8,
+    #[serde(alias = "entries")]
+    data: BTreeMap<String, JsonAlfalfaEntry>,
+}
+
+pub fn alfalfa_contents_to_json(contents: &AlfalfaContents) -> Result<String> {
+    let data = contents
+        .data
+        .iter()
+        .map(|(key, data)| {
+            let entry = match data {
+                AlfalfaEntryData::Binary(data) => {
+                    JsonAlfalfaEntry::Binary(BASE64_STANDARD.encode(data))
+                }
+                AlfalfaEntryData::Image(data) => {
+                    JsonAlfalfaEntry::Image(BASE64_STANDARD.encode(data))
+                }
+                AlfalfaEntryData::Erase(regions) => JsonAlfalfaEntry::Erase(regions.clone()),
+            };
+
+            (key.clone(), entry)
+        })
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&JsonAlfalfaDocument {
+        version: contents.version,
+        data,
+    })?)
+}
+
+pub fn alfalfa_contents_from_json(json: &str) -> Result<AlfalfaContents> {
+    let document: JsonAlfalfaDocument = serde_json::from_str(json)?;
+
+    let data = document
+        .data
+        .into_iter()
+        .map(|(key, entry)| {
+            let decode = |value: &str| {
+                BASE64_STANDARD
+                    .decode(value)
+                    .map_err(|source| AlfalfaInspectorError::Base64Error {
+                        key: key.clone(),
+                        source,
+                    })
+            };
+
+            let data = match entry {
+                JsonAlfalfaEntry::Binary(value) => AlfalfaEntryData::Binary(decode(&value)?),
+                JsonAlfalfaEntry::Image(value) => AlfalfaEntryData::Image(decode(&value)?),
+                JsonAlfalfaEntry::Erase(regions) => AlfalfaEntryData::Erase(regions),
+            };
+
+            Ok((key, data))
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(AlfalfaContents {
+        version: document.version,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_contents() -> AlfalfaContents {
+        AlfalfaContents {
+            data: AlfalfaDataMap::from([
+                ("cape".to_owned(), AlfalfaEntryData::Image(vec![1, 2, 3])),
+                ("custom".to_owned(), AlfalfaEntryData::Binary(vec![0, 255])),
+                (
+                    "erase".to_owned(),
+                    AlfalfaEntryData::Erase(vec![AlfalfaEraseEntryData {
+                        x: 0,
+                        y: 0,
+                        width: 8,
+                        height: 8,
+                    }]),
+                ),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn entries_are_stored_under_the_same_name_as_in_alfalfa_contents() {
+        let json = alfalfa_contents_to_json(&sample_contents()).unwrap();
+        let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(document["data"]["custom"]["type"], "binary");
+        assert_eq!(document["data"]["custom"]["value"], "AP8=");
+        assert!(document.get("entries").is_none());
+    }
+
+    #[test]
+    fn documents_using_the_old_entries_name_are_read() {
+        let json =
+            r#"{ "version": 1, "entries": { "custom": { "type": "binary", "value": "AP8=" } } }"#;
+
+        let contents = alfalfa_contents_from_json(json).unwrap();
+
+        assert_eq!(
+            contents.data.get("custom"),
+            Some(&AlfalfaEntryData::Binary(vec![0, 255]))
+        );
+    }
+}
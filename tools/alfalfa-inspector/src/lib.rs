@@ -1,15 +1,22 @@
-use ears_rs::alfalfa::{
-    read_alfalfa,
-    utils::{EraseRegion, EraseRegionsProvider},
-    AlfalfaData, AlfalfaDataKey,
-};
-use image::ImageFormat;
+use ears_rs::alfalfa::{read_alfalfa, AlfalfaData};
 use js_sys::Uint8Array;
 use js_utils::JsResult;
-use std::{collections::HashMap, io::Cursor};
+use serde::Serialize;
+use skin_utils::{alfalfa::alfalfa_capacity, optimize::SkinWriteOptions, png::PngEncodeOptions};
+use std::cell::RefCell;
 use wasm_bindgen::prelude::*;
 
-mod model;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod decoders;
+pub mod diff;
+pub mod errors;
+pub mod images;
+pub mod json;
+pub mod logic;
+pub mod model;
+pub mod salvage;
+pub mod transplant;
 
 use model::*;
 
@@ -17,10 +24,16 @@ use model::*;
 use lol_alloc::{AssumeSingleThreaded, FreeListAllocator};
 
 // SAFETY: This application is single threaded, so using AssumeSingleThreaded is allowed.
+#[cfg(target_arch = "wasm32")]
 #[global_allocator]
 static ALLOCATOR: AssumeSingleThreaded<FreeListAllocator> =
     unsafe { AssumeSingleThreaded::new(FreeListAllocator::new()) };
 
+thread_local! {
+    /// Decoders registered from JavaScript through [`register_alfalfa_decoder`].
+    static DECODERS: RefCell<decoders::DecoderRegistry> = RefCell::default();
+}
+
 #[wasm_bindgen]
 pub fn read_alfalfa_data(data: &[u8]) -> JsResult<JsValue> {
     #[cfg(debug_assertions)]
@@ -28,83 +41,238 @@ pub fn read_alfalfa_data(data: &[u8]) -> JsResult<JsValue> {
         console_error_panic_hook::set_once();
     }
 
-    let skin = image::load_from_memory(data)?.into_rgba8();
-    let mut map = HashMap::new();
-
-    let alfalfa = read_alfalfa(&skin)?;
-
-    if let Some(alfalfa_data) = alfalfa {
-        for (key, value) in alfalfa_data.get_data_raw() {
-            let key = key.to_owned();
-            let value = value.to_owned();
-
-            let entry_data = if key == Into::<&'static str>::into(AlfalfaDataKey::Cape)
-                || key == Into::<&'static str>::into(AlfalfaDataKey::Wings)
-            {
-                AlfalfaEntryData::Image(value)
-            } else if key == Into::<&'static str>::into(AlfalfaDataKey::Erase) {
-                let regions = alfalfa_data
-                    .get_erase_regions()?
-                    .unwrap_or_default()
-                    .into_iter()
-                    .map(|region| AlfalfaEraseEntryData {
-                        x: region.x,
-                        y: region.y,
-                        width: region.width,
-                        height: region.height,
-                    })
-                    .collect();
-
-                AlfalfaEntryData::Erase(regions)
-            } else {
-                AlfalfaEntryData::Binary(value)
-            };
-
-            map.insert(key, entry_data);
-        }
+    let contents = logic::read_alfalfa_contents_from_bytes(data)?;
+
+    serialize_alfalfa_data_map(contents.data)
+}
+
+/// Like [`read_alfalfa_data`], but returns `{ version, data }` so that the alfalfa version can be
+/// passed back to [`write_alfalfa_data`] unchanged.
+#[wasm_bindgen]
+pub fn read_alfalfa_data_with_version(data: &[u8]) -> JsResult<JsValue> {
+    #[cfg(debug_assertions)]
+    {
+        console_error_panic_hook::set_once();
     }
 
-    Ok(serialize_alfalfa_data_map(map)?)
+    let contents = logic::read_alfalfa_contents_from_bytes(data)?;
+
+    serialize_alfalfa_contents(contents)
 }
 
 #[wasm_bindgen]
 pub fn write_alfalfa_data(image_data: &[u8], workspace: JsValue) -> JsResult<Uint8Array> {
+    write_alfalfa_data_with_options(image_data, workspace, JsValue::UNDEFINED)
+}
+
+/// Like [`write_alfalfa_data`], but with control over the PNG compression and filter,
+/// over whether the metadata of the original file is kept, and over whether image entries
+/// are re-encoded as small as possible first.
+#[wasm_bindgen]
+pub fn write_alfalfa_data_with_options(
+    image_data: &[u8],
+    workspace: JsValue,
+    options: JsValue,
+) -> JsResult<Uint8Array> {
+    let result = write_alfalfa_data_with_report_impl(image_data, workspace, options)?;
+
+    Ok(result.skin.as_slice().into())
+}
+
+/// Like [`write_alfalfa_data_with_options`], but also reports how many bytes re-encoding the
+/// image entries saved.
+#[wasm_bindgen]
+pub fn write_alfalfa_data_with_report(
+    image_data: &[u8],
+    workspace: JsValue,
+    options: JsValue,
+) -> JsResult<JsValue> {
+    let result = write_alfalfa_data_with_report_impl(image_data, workspace, options)?;
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+fn write_alfalfa_data_with_report_impl(
+    image_data: &[u8],
+    workspace: JsValue,
+    options: JsValue,
+) -> JsResult<AlfalfaWriteResult> {
     console_error_panic_hook::set_once();
 
-    let map = deserialize_alfalfa_data_map(workspace)?;
-    let mut alfalfa = AlfalfaData::new();
-
-    for (key, data) in map {
-        if &key == Into::<&'static str>::into(AlfalfaDataKey::Erase) {
-            if let AlfalfaEntryData::Erase(data) = data {
-                let mut regions = Vec::new();
-
-                for region in data {
-                    regions.push(EraseRegion {
-                        x: region.x,
-                        y: region.y,
-                        width: region.width,
-                        height: region.height,
-                    });
-                }
-
-                alfalfa.set_erase_regions(&regions)?;
-            }
-        } else if let AlfalfaEntryData::Binary(data) | AlfalfaEntryData::Image(data) = data {
-            let key = Box::leak(key.into_boxed_str());
-
-            alfalfa.set_data(AlfalfaDataKey::Custom(key), data);
-        }
-    }
+    let options: SkinWriteOptions =
+        serde_wasm_bindgen::from_value::<Option<_>>(options)?.unwrap_or_default();
 
-    let mut skin = image::load_from_memory(image_data)?.into_rgba8();
+    let mut contents = deserialize_alfalfa_contents(workspace)?;
 
-    ears_rs::alfalfa::write_alfalfa(&alfalfa, &mut skin)?;
+    let optimization = if options.optimize_images {
+        images::optimize_image_entries(&mut contents)
+    } else {
+        Default::default()
+    };
 
-    let mut bytes = Vec::new();
-    {
-        skin.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
-    }
+    let skin = logic::write_alfalfa_contents_to_bytes(image_data, contents, &options.png)?;
+
+    Ok(AlfalfaWriteResult { skin, optimization })
+}
+
+/// Reports how much alfalfa space the skin has and how much `workspace` would take up, so that
+/// callers can check whether it fits before writing. When `workspace` is `undefined`, the alfalfa
+/// data already stored in the skin is measured instead.
+#[wasm_bindgen]
+pub fn get_alfalfa_capacity(image_data: &[u8], workspace: JsValue) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let skin = image::load_from_memory(image_data)?.into_rgba8();
+
+    let alfalfa = if workspace.is_undefined() || workspace.is_null() {
+        read_alfalfa(&skin)?.unwrap_or_else(AlfalfaData::new)
+    } else {
+        logic::alfalfa_from_contents(deserialize_alfalfa_contents(workspace)?)?
+    };
+
+    let report = alfalfa_capacity(&skin, &alfalfa);
+
+    Ok(serde_wasm_bindgen::to_value(&report)?)
+}
+
+/// Reads the alfalfa data of a skin as the JSON document described in [`json`].
+#[wasm_bindgen]
+pub fn export_alfalfa_json(image_data: &[u8]) -> JsResult<String> {
+    console_error_panic_hook::set_once();
+
+    let contents = logic::read_alfalfa_contents_from_bytes(image_data)?;
+
+    Ok(json::alfalfa_contents_to_json(&contents)?)
+}
+
+/// Replaces the alfalfa data of a skin with the contents of a JSON document described in [`json`].
+#[wasm_bindgen]
+pub fn import_alfalfa_json(image_data: &[u8], json: &str) -> JsResult<Uint8Array> {
+    console_error_panic_hook::set_once();
+
+    let contents = json::alfalfa_contents_from_json(json)?;
+
+    let bytes =
+        logic::write_alfalfa_contents_to_bytes(image_data, contents, &PngEncodeOptions::default())?;
 
     Ok(bytes.as_slice().into())
 }
+
+/// Decodes the image entries (such as the cape and wings) of a skin, reporting their size and
+/// colour type, and whether Ears will accept them.
+#[wasm_bindgen]
+pub fn inspect_alfalfa_images(image_data: &[u8]) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let contents = logic::read_alfalfa_contents_from_bytes(image_data)?;
+    let infos = images::inspect_image_entries(&contents);
+
+    Ok(serde_wasm_bindgen::to_value(&infos)?)
+}
+
+/// Renders the image entry stored under `key` as a PNG, scaled up by `scale`.
+#[wasm_bindgen]
+pub fn render_alfalfa_image_preview(
+    image_data: &[u8],
+    key: &str,
+    scale: u32,
+) -> JsResult<Uint8Array> {
+    console_error_panic_hook::set_once();
+
+    let contents = logic::read_alfalfa_contents_from_bytes(image_data)?;
+    let bytes = images::render_image_entry_preview(&contents, key, scale)?;
+
+    Ok(bytes.as_slice().into())
+}
+
+/// Compares the alfalfa data of two skins, listing added, removed and modified entries.
+#[wasm_bindgen]
+pub fn diff_alfalfa_data(before_image_data: &[u8], after_image_data: &[u8]) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let before = logic::read_alfalfa_contents_from_bytes(before_image_data)?;
+    let after = logic::read_alfalfa_contents_from_bytes(after_image_data)?;
+
+    let diff = diff::diff_alfalfa_contents(&before, &after)?;
+
+    Ok(serde_wasm_bindgen::to_value(&diff)?)
+}
+
+/// Copies the alfalfa data of a donor skin into a target skin, returning the new target skin and
+/// a report of which entries ended up where.
+#[wasm_bindgen]
+pub fn transplant_alfalfa_data(
+    donor_image_data: &[u8],
+    target_image_data: &[u8],
+    options: JsValue,
+) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let options: Option<transplant::TransplantOptions> = serde_wasm_bindgen::from_value(options)?;
+
+    let result = transplant::transplant_alfalfa_bytes(
+        donor_image_data,
+        target_image_data,
+        &options.unwrap_or_default(),
+    )?;
+
+    // The flattened report is serialized as a map, which should still reach JS as a plain object.
+    let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+
+    Ok(result.serialize(&serializer)?)
+}
+
+/// Recovers what it can from alfalfa data that [`read_alfalfa_data`] refuses, and reports where
+/// and why decoding failed.
+#[wasm_bindgen]
+pub fn diagnose_alfalfa_data(image_data: &[u8]) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let skin = image::load_from_memory(image_data)?.into_rgba8();
+    let report = salvage::salvage_alfalfa(&skin);
+
+    let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+
+    Ok(report.serialize(&serializer)?)
+}
+
+/// Renders a PNG highlighting the pixels that carry alfalfa bits, and where decoding failed.
+#[wasm_bindgen]
+pub fn render_alfalfa_diagnostic(image_data: &[u8], scale: u32) -> JsResult<Uint8Array> {
+    console_error_panic_hook::set_once();
+
+    let skin = image::load_from_memory(image_data)?.into_rgba8();
+    let bytes = salvage::render_salvage_visualisation(&skin, scale)?;
+
+    Ok(bytes.as_slice().into())
+}
+
+/// Registers a [`decoders::LayoutDecoder`] for a custom key, used by
+/// [`inspect_alfalfa_binary_entries`] from then on.
+#[wasm_bindgen]
+pub fn register_alfalfa_decoder(layout: JsValue) -> JsResult<()> {
+    console_error_panic_hook::set_once();
+
+    let layout: decoders::LayoutDecoder = serde_wasm_bindgen::from_value(layout)?;
+
+    DECODERS.with_borrow_mut(|registry| registry.register(Box::new(layout)));
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn clear_alfalfa_decoders() {
+    DECODERS.with_borrow_mut(|registry| registry.clear());
+}
+
+/// Breaks down every binary entry of a skin, using the registered decoders where possible and a
+/// hexdump otherwise.
+#[wasm_bindgen]
+pub fn inspect_alfalfa_binary_entries(image_data: &[u8]) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let contents = logic::read_alfalfa_contents_from_bytes(image_data)?;
+    let inspections = DECODERS.with_borrow(|registry| registry.inspect_binary_entries(&contents));
+
+    Ok(serde_wasm_bindgen::to_value(&inspections)?)
+}
@@ -0,0 +1,292 @@
+use ears_rs::alfalfa::{
+    read_alfalfa,
+    utils::{EraseRegion, EraseRegionsProvider},
+    AlfalfaData, AlfalfaDataKey,
+};
+use image::RgbaImage;
+use skin_utils::png::{encode_png, PngEncodeOptions};
+use std::collections::HashMap;
+
+use crate::{errors::*, model::*};
+
+/// The alfalfa version written by this crate, and the only one it knows how to interpret.
+pub fn current_alfalfa_version() -> u8 {
+    AlfalfaData::new().into_raw().0
+}
+
+/// Refuses alfalfa versions this crate does not understand, rather than silently rewriting them.
+pub fn check_alfalfa_version(version: u8) -> Result<()> {
+    let supported = current_alfalfa_version();
+
+    if version != supported {
+        return Err(AlfalfaInspectorError::UnsupportedVersion { version, supported });
+    }
+
+    Ok(())
+}
+
+/// Reads the alfalfa data stored in `skin`. Skins without alfalfa data read as empty contents of
+/// the current version.
+pub fn read_alfalfa_contents(skin: &RgbaImage) -> Result<AlfalfaContents> {
+    match read_alfalfa(skin)? {
+        Some(alfalfa_data) => contents_from_alfalfa(alfalfa_data),
+        None => Ok(AlfalfaContents::default()),
+    }
+}
+
+/// Sorts the raw entries of `alfalfa_data` into binary, image and erase entries.
+pub fn contents_from_alfalfa(alfalfa_data: AlfalfaData) -> Result<AlfalfaContents> {
+    let mut data = AlfalfaDataMap::new();
+
+    for (key, value) in alfalfa_data.get_data_raw() {
+        let key = key.to_owned();
+        let value = value.to_owned();
+
+        let entry_data = if key == Into::<&'static str>::into(AlfalfaDataKey::Cape)
+            || key == Into::<&'static str>::into(AlfalfaDataKey::Wings)
+        {
+            AlfalfaEntryData::Image(value)
+        } else if key == Into::<&'static str>::into(AlfalfaDataKey::Erase) {
+            let regions = alfalfa_data
+                .get_erase_regions()?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|region| AlfalfaEraseEntryData {
+                    x: region.x,
+                    y: region.y,
+                    width: region.width,
+                    height: region.height,
+                })
+                .collect();
+
+            AlfalfaEntryData::Erase(regions)
+        } else {
+            AlfalfaEntryData::Binary(value)
+        };
+
+        data.insert(key, entry_data);
+    }
+
+    Ok(AlfalfaContents {
+        version: alfalfa_data.into_raw().0,
+        data,
+    })
+}
+
+/// Reads the alfalfa data stored in a skin file.
+pub fn read_alfalfa_contents_from_bytes(skin_bytes: &[u8]) -> Result<AlfalfaContents> {
+    let skin = image::load_from_memory(skin_bytes)?.into_rgba8();
+
+    read_alfalfa_contents(&skin)
+}
+
+/// Maps a key name back to the [`AlfalfaDataKey`] Ears knows it by, if it is one of the well-known keys.
+pub fn known_alfalfa_key(key: &str) -> Option<AlfalfaDataKey> {
+    if key == Into::<&'static str>::into(AlfalfaDataKey::Erase) {
+        Some(AlfalfaDataKey::Erase)
+    } else if key == Into::<&'static str>::into(AlfalfaDataKey::Wings) {
+        Some(AlfalfaDataKey::Wings)
+    } else if key == Into::<&'static str>::into(AlfalfaDataKey::Cape) {
+        Some(AlfalfaDataKey::Cape)
+    } else {
+        None
+    }
+}
+
+/// Builds the [`AlfalfaData`] that `contents` describes, refusing versions this crate doesn't understand.
+///
+/// Well-known keys are written through their [`AlfalfaDataKey`] variant, while any other key is
+/// kept as-is in the raw data, so that no `'static` key has to be made up for it.
+pub fn alfalfa_from_contents(contents: AlfalfaContents) -> Result<AlfalfaData> {
+    check_alfalfa_version(contents.version)?;
+
+    let mut custom_entries = HashMap::new();
+    let mut known_entries = Vec::new();
+    let mut erase_regions = None;
+
+    for (key, data) in contents.data {
+        match (known_alfalfa_key(&key), data) {
+            (Some(AlfalfaDataKey::Erase), AlfalfaEntryData::Erase(regions)) => {
+                erase_regions = Some(
+                    regions
+                        .into_iter()
+                        .map(|region| EraseRegion {
+                            x: region.x,
+                            y: region.y,
+                            width: region.width,
+                            height: region.height,
+                        })
+                        .collect::<Vec<_>>(),
+                );
+            }
+            // Erase regions only have a meaning under the erase key.
+            (_, AlfalfaEntryData::Erase(_)) => {}
+            (Some(known_key), AlfalfaEntryData::Binary(data) | AlfalfaEntryData::Image(data)) => {
+                known_entries.push((known_key, data));
+            }
+            (None, AlfalfaEntryData::Binary(data) | AlfalfaEntryData::Image(data)) => {
+                custom_entries.insert(key, data);
+            }
+        }
+    }
+
+    let mut alfalfa = AlfalfaData::new_raw(contents.version, custom_entries);
+
+    for (key, data) in known_entries {
+        alfalfa.set_data(key, data);
+    }
+
+    if let Some(regions) = erase_regions {
+        alfalfa.set_erase_regions(&regions)?;
+    }
+
+    Ok(alfalfa)
+}
+
+/// Replaces the alfalfa data in `skin` with `contents`.
+pub fn write_alfalfa_contents(skin: &mut RgbaImage, contents: AlfalfaContents) -> Result<()> {
+    let alfalfa = alfalfa_from_contents(contents)?;
+
+    ears_rs::alfalfa::write_alfalfa(&alfalfa, skin)?;
+
+    Ok(())
+}
+
+/// Replaces the alfalfa data in a skin file with `contents`, returning the new file.
+pub fn write_alfalfa_contents_to_bytes(
+    skin_bytes: &[u8],
+    contents: AlfalfaContents,
+    options: &PngEncodeOptions,
+) -> Result<Vec<u8>> {
+    let mut skin = image::load_from_memory(skin_bytes)?.into_rgba8();
+
+    write_alfalfa_contents(&mut skin, contents)?;
+
+    Ok(encode_png(&skin, Some(skin_bytes), options)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_contents() -> AlfalfaContents {
+        let data = AlfalfaDataMap::from([
+            ("cape".to_owned(), AlfalfaEntryData::Image(vec![1, 2, 3, 4])),
+            ("wings".to_owned(), AlfalfaEntryData::Image(vec![5, 6, 7])),
+            (
+                "erase".to_owned(),
+                AlfalfaEntryData::Erase(vec![
+                    AlfalfaEraseEntryData {
+                        x: 0,
+                        y: 0,
+                        width: 8,
+                        height: 8,
+                    },
+                    AlfalfaEraseEntryData {
+                        x: 40,
+                        y: 16,
+                        width: 4,
+                        height: 12,
+                    },
+                ]),
+            ),
+            (
+                "custom".to_owned(),
+                AlfalfaEntryData::Binary(vec![0, 255, 42]),
+            ),
+        ]);
+
+        AlfalfaContents {
+            version: current_alfalfa_version(),
+            data,
+        }
+    }
+
+    #[test]
+    fn known_keys_map_to_their_variants() {
+        assert!(matches!(
+            known_alfalfa_key("erase"),
+            Some(AlfalfaDataKey::Erase)
+        ));
+        assert!(matches!(
+            known_alfalfa_key("wings"),
+            Some(AlfalfaDataKey::Wings)
+        ));
+        assert!(matches!(
+            known_alfalfa_key("cape"),
+            Some(AlfalfaDataKey::Cape)
+        ));
+        assert!(known_alfalfa_key("custom").is_none());
+        assert!(known_alfalfa_key("Cape").is_none());
+    }
+
+    #[test]
+    fn known_and_custom_keys_are_stored_under_their_names() {
+        let alfalfa = alfalfa_from_contents(sample_contents()).unwrap();
+
+        assert_eq!(
+            alfalfa.get_data(AlfalfaDataKey::Cape),
+            Some(&[1, 2, 3, 4][..])
+        );
+        assert_eq!(
+            alfalfa.get_data(AlfalfaDataKey::Wings),
+            Some(&[5, 6, 7][..])
+        );
+        assert_eq!(
+            alfalfa.get_data_raw().get("custom").map(Vec::as_slice),
+            Some(&[0, 255, 42][..])
+        );
+        assert_eq!(
+            alfalfa.get_erase_regions().unwrap().map(|r| r.len()),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn read_write_read_is_lossless() {
+        let mut skin = RgbaImage::new(64, 64);
+        write_alfalfa_contents(&mut skin, sample_contents()).unwrap();
+
+        let first_read = read_alfalfa_contents(&skin).unwrap();
+        assert_eq!(first_read, sample_contents());
+
+        let mut rewritten = RgbaImage::new(64, 64);
+        write_alfalfa_contents(&mut rewritten, first_read.clone()).unwrap();
+
+        assert_eq!(read_alfalfa_contents(&rewritten).unwrap(), first_read);
+        assert_eq!(rewritten, skin);
+    }
+
+    #[test]
+    fn skin_without_alfalfa_reads_as_empty() {
+        let skin = RgbaImage::new(64, 64);
+
+        let contents = read_alfalfa_contents(&skin).unwrap();
+
+        assert!(contents.data.is_empty());
+        assert_eq!(contents.version, current_alfalfa_version());
+    }
+
+    #[test]
+    fn unknown_versions_are_refused() {
+        let contents = AlfalfaContents {
+            version: current_alfalfa_version().wrapping_add(1),
+            ..sample_contents()
+        };
+
+        assert!(matches!(
+            alfalfa_from_contents(contents),
+            Err(AlfalfaInspectorError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn json_round_trip_is_lossless() {
+        let json = crate::json::alfalfa_contents_to_json(&sample_contents()).unwrap();
+
+        assert_eq!(
+            crate::json::alfalfa_contents_from_json(&json).unwrap(),
+            sample_contents()
+        );
+    }
+}
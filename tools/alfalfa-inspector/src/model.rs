@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use js_sys::{Object, Reflect};
 use js_utils::JsResult;
 use serde::{Deserialize, Serialize};
+use skin_utils::optimize::ImageOptimizationReport;
 use wasm_bindgen::{JsValue, JsError};
 
 
@@ -27,6 +28,30 @@ pub enum AlfalfaEntryData {
 
 pub type AlfalfaDataMap = HashMap<String, AlfalfaEntryData>;
 
+/// Everything stored in a skin's alfalfa payload, shaped like ears-manipulator's `WasmAlfalfaData`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlfalfaContents {
+    pub version: u8,
+    pub data: AlfalfaDataMap,
+}
+
+impl Default for AlfalfaContents {
+    fn default() -> Self {
+        Self {
+            version: crate::logic::current_alfalfa_version(),
+            data: AlfalfaDataMap::new(),
+        }
+    }
+}
+
+/// A re-encoded skin, along with what optimizing its image entries saved.
+#[derive(Debug, Clone, Serialize)]
+pub struct AlfalfaWriteResult {
+    #[serde(with = "serde_bytes")]
+    pub skin: Vec<u8>,
+    pub optimization: ImageOptimizationReport,
+}
+
 pub(crate) fn serialize_alfalfa_data_map(data: AlfalfaDataMap) -> JsResult<JsValue> {
     fn serialize_alfalfa_entry_data(data: AlfalfaEntryData) -> JsResult<JsValue> {
         Ok(data.serialize(&serde_wasm_bindgen::Serializer::default())?)
@@ -41,6 +66,47 @@ pub(crate) fn serialize_alfalfa_data_map(data: AlfalfaDataMap) -> JsResult<JsVal
     Ok(obj.into())
 }
 
+pub(crate) fn serialize_alfalfa_contents(contents: AlfalfaContents) -> JsResult<JsValue> {
+    let obj = Object::new();
+
+    Reflect::set(&obj, &"version".into(), &contents.version.into())
+        .map_err(|_| JsError::new("Failed to set version"))?;
+    Reflect::set(
+        &obj,
+        &"data".into(),
+        &serialize_alfalfa_data_map(contents.data)?,
+    )
+    .map_err(|_| JsError::new("Failed to set data"))?;
+
+    Ok(obj.into())
+}
+
+/// Accepts both `{ version, data }` and, for older callers, a bare entry map (which is assumed to
+/// be of the current version).
+pub(crate) fn deserialize_alfalfa_contents(data: JsValue) -> JsResult<AlfalfaContents> {
+    let version = Reflect::get(&data, &"version".into())
+        .map_err(|_| JsError::new("Failed to get version"))?;
+
+    let Some(version) = version.as_f64() else {
+        return Ok(AlfalfaContents {
+            data: deserialize_alfalfa_data_map(data)?,
+            ..Default::default()
+        });
+    };
+
+    if version.fract() != 0.0 || !(0.0..=u8::MAX as f64).contains(&version) {
+        return Err(JsError::new("Invalid alfalfa version"));
+    }
+
+    let entries =
+        Reflect::get(&data, &"data".into()).map_err(|_| JsError::new("Failed to get data"))?;
+
+    Ok(AlfalfaContents {
+        version: version as u8,
+        data: deserialize_alfalfa_data_map(entries)?,
+    })
+}
+
 pub(crate) fn deserialize_alfalfa_data_map(data: JsValue) -> JsResult<AlfalfaDataMap> {
     fn deserialize_alfalfa_entry_data(data: JsValue) -> JsResult<AlfalfaEntryData> {
         let data = serde_wasm_bindgen::from_value(data).map_err(|_| JsError::new("Failed to deserialize data"))?;
@@ -0,0 +1,448 @@
+//! A best-effort reader for alfalfa data that [`ears_rs::alfalfa::read_alfalfa`] rejects.
+//!
+//! This mirrors the layout the Ears mod uses: 7 bits are stored in the alpha channel of every
+//! base-layer pixel (which Minecraft ignores), walked column by column, with a fully opaque pixel
+//! holding zero. The resulting byte stream starts with a magic number and a version, followed by
+//! entries made of a zero-terminated ASCII key, a big-endian 16-bit length and the entry data, and
+//! ends with an empty key. Unlike the real reader, this one keeps whatever it read before things
+//! went wrong, and remembers where that happened.
+//!
+//! ears-rs doesn't expose its bit layout, so the tests below pin this copy of it to the output of
+//! [`ears_rs::alfalfa::write_alfalfa`].
+
+use std::{collections::HashMap, io::Cursor, ops::Range};
+
+use ears_rs::alfalfa::AlfalfaData;
+use image::{imageops::FilterType, ImageFormat, Rgba, RgbaImage};
+use serde::Serialize;
+
+use crate::{errors::*, images::MAX_PREVIEW_SCALE, logic, model::*};
+
+/// The first four bytes of every alfalfa payload.
+pub const ALFALFA_MAGIC: u32 = 0xEA1F_A1FA;
+
+/// Number of payload bits stored in each carrier pixel.
+const BITS_PER_PIXEL: usize = 7;
+
+/// The areas of a 64x64 skin that vanilla forces opaque, as `(x, y, width, height)`.
+const CARRIER_AREAS: [(u32, u32, u32, u32); 3] =
+    [(0, 0, 32, 16), (0, 16, 64, 16), (16, 48, 32, 16)];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SalvagedEntry {
+    pub key: String,
+    /// How many bytes the entry claims to have.
+    pub declared_size: usize,
+    /// How many of those were actually present.
+    pub recovered_size: usize,
+    pub complete: bool,
+}
+
+/// Where and why decoding stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SalvageFailure {
+    pub reason: String,
+    /// Offset into the decoded byte stream.
+    pub byte_offset: usize,
+    /// The pixel carrying the first bit of that byte.
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlfalfaSalvageReport {
+    /// Whether [`ears_rs::alfalfa::read_alfalfa`] accepts the data as-is.
+    pub readable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_error: Option<String>,
+    pub magic_found: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub declared_version: Option<u8>,
+    /// Every entry that was found, including the one decoding failed in.
+    pub entries: Vec<SalvagedEntry>,
+    /// The complete entries, ready to be written back with [`logic::write_alfalfa_contents`].
+    pub recovered: AlfalfaContents,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<SalvageFailure>,
+    /// Number of pixels that can carry alfalfa bits.
+    pub carrier_pixels: usize,
+    /// Number of bytes of the stream that were looked at.
+    pub bytes_read: usize,
+}
+
+/// How far decoding got, as byte ranges of the stream.
+struct SalvageProgress {
+    header: Range<usize>,
+    entries: Range<usize>,
+    failed: Range<usize>,
+}
+
+/// The pixels that can carry alfalfa bits, in the order their bits are read.
+pub fn carrier_pixels() -> Vec<(u32, u32)> {
+    (0..64)
+        .flat_map(|x| (0..64).map(move |y| (x, y)))
+        .filter(|&(x, y)| {
+            CARRIER_AREAS
+                .iter()
+                .any(|&(area_x, area_y, width, height)| {
+                    (area_x..area_x + width).contains(&x) && (area_y..area_y + height).contains(&y)
+                })
+        })
+        .collect()
+}
+
+fn read_stream(skin: &RgbaImage, carriers: &[(u32, u32)]) -> Vec<u8> {
+    let mut stream = vec![0u8; carriers.len() * BITS_PER_PIXEL / 8];
+
+    for (index, &(x, y)) in carriers.iter().enumerate() {
+        let alpha = skin
+            .get_pixel_checked(x, y)
+            .map_or(0xFF, |pixel| pixel.0[3]);
+        let value = 0x7F - (alpha & 0x7F);
+
+        for bit in 0..BITS_PER_PIXEL {
+            let position = index * BITS_PER_PIXEL + bit;
+
+            if value & (1 << bit) != 0 && position / 8 < stream.len() {
+                stream[position / 8] |= 1 << (position % 8);
+            }
+        }
+    }
+
+    stream
+}
+
+/// Overwrites one byte of the alfalfa stream in `skin`, using the same layout as [`read_stream`].
+#[cfg(test)]
+pub(crate) fn write_stream_byte(skin: &mut RgbaImage, byte_offset: usize, byte: u8) {
+    let carriers = carrier_pixels();
+
+    for bit in 0..8 {
+        let position = byte_offset * 8 + bit;
+        let (x, y) = carriers[position / BITS_PER_PIXEL];
+        let mask = 1 << (position % BITS_PER_PIXEL);
+
+        let alpha = &mut skin.get_pixel_mut(x, y).0[3];
+        // Carried bits are stored inverted, so a set bit clears the alpha bit.
+        if byte & (1 << bit) != 0 {
+            *alpha &= !mask;
+        } else {
+            *alpha |= mask;
+        }
+    }
+}
+
+fn carrier_for_offset(carriers: &[(u32, u32)], byte_offset: usize) -> (u32, u32) {
+    let index = (byte_offset * 8 / BITS_PER_PIXEL).min(carriers.len().saturating_sub(1));
+
+    carriers.get(index).copied().unwrap_or_default()
+}
+
+fn decode(
+    stream: &[u8],
+    carriers: &[(u32, u32)],
+    report: &mut AlfalfaSalvageReport,
+) -> SalvageProgress {
+    let mut progress = SalvageProgress {
+        header: 0..0,
+        entries: 0..0,
+        failed: 0..0,
+    };
+
+    let fail = |report: &mut AlfalfaSalvageReport, reason: String, byte_offset: usize| {
+        let (x, y) = carrier_for_offset(carriers, byte_offset);
+
+        report.failure = Some(SalvageFailure {
+            reason,
+            byte_offset,
+            x,
+            y,
+        });
+    };
+
+    if stream.get(..4) != Some(&ALFALFA_MAGIC.to_be_bytes()[..]) {
+        return progress;
+    }
+
+    report.magic_found = true;
+
+    let Some(&version) = stream.get(4) else {
+        fail(report, "The data ends before its version".into(), 4);
+        return progress;
+    };
+
+    report.declared_version = Some(version);
+    progress.header = 0..5;
+    progress.entries = 5..5;
+
+    if logic::check_alfalfa_version(version).is_err() {
+        fail(report, format!("Version {version} is not supported"), 4);
+        progress.failed = 4..5;
+        return progress;
+    }
+
+    let mut offset = 5;
+    let mut recovered = HashMap::new();
+
+    loop {
+        let entry_start = offset;
+
+        let Some(key_length) = stream[offset..].iter().position(|&b| b == 0) else {
+            fail(
+                report,
+                "The data ends in the middle of a key".into(),
+                entry_start,
+            );
+            progress.failed = entry_start..stream.len();
+            break;
+        };
+
+        let key_bytes = &stream[offset..offset + key_length];
+        offset += key_length + 1;
+
+        if key_bytes.is_empty() {
+            progress.entries.end = offset;
+            break;
+        }
+
+        if !key_bytes.iter().all(|b| b.is_ascii_graphic()) {
+            fail(
+                report,
+                "Found a key that is not printable ASCII".into(),
+                entry_start,
+            );
+            progress.failed = entry_start..offset;
+            break;
+        }
+
+        let key = String::from_utf8_lossy(key_bytes).into_owned();
+
+        let Some(length_bytes) = stream.get(offset..offset + 2) else {
+            fail(
+                report,
+                format!("The data ends before the length of {key:?}"),
+                offset,
+            );
+            progress.failed = entry_start..stream.len();
+            break;
+        };
+
+        let declared_size = u16::from_be_bytes([length_bytes[0], length_bytes[1]]) as usize;
+        offset += 2;
+
+        let available = stream.len() - offset;
+        let recovered_size = declared_size.min(available);
+
+        report.entries.push(SalvagedEntry {
+            key: key.clone(),
+            declared_size,
+            recovered_size,
+            complete: recovered_size == declared_size,
+        });
+
+        if recovered_size < declared_size {
+            fail(
+                report,
+                format!("{key:?} claims {declared_size} bytes, but only {available} are left"),
+                offset,
+            );
+            progress.failed = entry_start..stream.len();
+            break;
+        }
+
+        recovered.insert(key, stream[offset..offset + declared_size].to_vec());
+        offset += declared_size;
+        progress.entries.end = offset;
+    }
+
+    report.recovered = salvaged_contents(version, recovered);
+    report.bytes_read = progress.entries.end.max(progress.failed.end);
+
+    progress
+}
+
+/// Sorts the salvaged entries into typed ones, keeping an entry as binary when its data doesn't
+/// parse as the type its key implies.
+fn salvaged_contents(version: u8, entries: HashMap<String, Vec<u8>>) -> AlfalfaContents {
+    let mut contents = AlfalfaContents {
+        version,
+        data: AlfalfaDataMap::new(),
+    };
+
+    for (key, value) in entries {
+        let single = AlfalfaData::new_raw(version, HashMap::from([(key.clone(), value.clone())]));
+
+        let entry = logic::contents_from_alfalfa(single)
+            .ok()
+            .and_then(|mut typed| typed.data.remove(&key))
+            .unwrap_or(AlfalfaEntryData::Binary(value));
+
+        contents.data.insert(key, entry);
+    }
+
+    contents
+}
+
+fn salvage(skin: &RgbaImage) -> (AlfalfaSalvageReport, SalvageProgress) {
+    let carriers = carrier_pixels();
+
+    let read_error = ears_rs::alfalfa::read_alfalfa(skin).err();
+
+    let mut report = AlfalfaSalvageReport {
+        readable: read_error.is_none(),
+        read_error: read_error.map(|err| err.to_string()),
+        magic_found: false,
+        declared_version: None,
+        entries: Vec::new(),
+        recovered: AlfalfaContents::default(),
+        failure: None,
+        carrier_pixels: carriers.len(),
+        bytes_read: 0,
+    };
+
+    let stream = read_stream(skin, &carriers);
+    let progress = decode(&stream, &carriers, &mut report);
+
+    (report, progress)
+}
+
+/// Recovers as much alfalfa data from `skin` as possible, and explains where decoding failed.
+pub fn salvage_alfalfa(skin: &RgbaImage) -> AlfalfaSalvageReport {
+    salvage(skin).0
+}
+
+/// Draws the carrier pixels of `skin` over a dimmed copy of it, scaled up by `scale`: the header
+/// in cyan, intact entries in green, the bytes decoding failed on in red, and the pixel it failed
+/// at in magenta.
+pub fn render_salvage_visualisation(skin: &RgbaImage, scale: u32) -> Result<Vec<u8>> {
+    if !(1..=MAX_PREVIEW_SCALE).contains(&scale) {
+        return Err(AlfalfaInspectorError::InvalidScale { scale });
+    }
+
+    let (report, progress) = salvage(skin);
+    let carriers = carrier_pixels();
+
+    let mut result = RgbaImage::from_fn(skin.width(), skin.height(), |x, y| {
+        let [r, g, b, _] = skin.get_pixel(x, y).0;
+        Rgba([r / 4, g / 4, b / 4, 255])
+    });
+
+    for (index, &(x, y)) in carriers.iter().enumerate() {
+        let byte_offset = index * BITS_PER_PIXEL / 8;
+
+        let color = if progress.failed.contains(&byte_offset) {
+            Rgba([255, 48, 48, 255])
+        } else if progress.entries.contains(&byte_offset) {
+            Rgba([48, 220, 72, 255])
+        } else if progress.header.contains(&byte_offset) {
+            Rgba([48, 200, 255, 255])
+        } else {
+            continue;
+        };
+
+        if x < result.width() && y < result.height() {
+            result.put_pixel(x, y, color);
+        }
+    }
+
+    if let Some(SalvageFailure { x, y, .. }) = report.failure {
+        if x < result.width() && y < result.height() {
+            result.put_pixel(x, y, Rgba([255, 0, 255, 255]));
+        }
+    }
+
+    let preview = image::imageops::resize(
+        &result,
+        result.width() * scale,
+        result.height() * scale,
+        FilterType::Nearest,
+    );
+
+    let mut bytes = Vec::new();
+    preview.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use ears_rs::alfalfa::{write_alfalfa, AlfalfaDataKey};
+
+    use super::*;
+
+    fn sample_alfalfa() -> AlfalfaData {
+        let mut alfalfa = AlfalfaData::new();
+        alfalfa.set_data(AlfalfaDataKey::Cape, vec![0x89, b'P', b'N', b'G', 1, 2, 3]);
+        // Large enough to use nearly every carrier pixel, so the whole layout is checked.
+        alfalfa.set_data(
+            AlfalfaDataKey::Custom("custom"),
+            (0..1700).map(|i| (i % 251) as u8).collect(),
+        );
+        alfalfa.set_data(AlfalfaDataKey::Erase, vec![0, 0, 8, 8]);
+
+        alfalfa
+    }
+
+    fn sample_skin() -> RgbaImage {
+        let mut skin = RgbaImage::from_pixel(64, 64, Rgba([0x40, 0x80, 0xC0, 0xFF]));
+        write_alfalfa(&sample_alfalfa(), &mut skin).unwrap();
+
+        skin
+    }
+
+    #[test]
+    fn written_alfalfa_is_recovered_completely() {
+        let report = salvage_alfalfa(&sample_skin());
+
+        assert!(report.readable);
+        assert!(report.magic_found);
+        assert_eq!(report.failure, None);
+        assert_eq!(
+            report.declared_version,
+            Some(logic::current_alfalfa_version())
+        );
+        assert!(report.entries.iter().all(|entry| entry.complete));
+
+        let expected = logic::contents_from_alfalfa(sample_alfalfa()).unwrap();
+        assert_eq!(report.recovered, expected);
+    }
+
+    #[test]
+    fn entries_before_a_corrupted_length_are_recovered() {
+        let mut skin = sample_skin();
+        let clean = salvage_alfalfa(&skin);
+
+        // Find the length field of the last entry from the layout of the clean stream.
+        let (last, previous) = clean.entries.split_last().unwrap();
+        let length_offset = 5
+            + previous
+                .iter()
+                .map(|entry| entry.key.len() + 1 + 2 + entry.declared_size)
+                .sum::<usize>()
+            + last.key.len()
+            + 1;
+
+        write_stream_byte(&mut skin, length_offset, 0xFF);
+
+        let report = salvage_alfalfa(&skin);
+
+        assert!(!report.readable);
+        assert!(report.magic_found);
+        assert_eq!(report.entries.len(), clean.entries.len());
+        assert!(report.entries[..previous.len()]
+            .iter()
+            .all(|entry| entry.complete));
+
+        let corrupted = report.entries.last().unwrap();
+        assert_eq!(corrupted.key, last.key);
+        assert!(!corrupted.complete);
+
+        let failure = report.failure.unwrap();
+        assert_eq!(failure.byte_offset, length_offset + 2);
+        assert_eq!(report.recovered.data.len(), previous.len());
+        assert!(!report.recovered.data.contains_key(&last.key));
+    }
+}
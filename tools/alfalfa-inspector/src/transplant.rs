@@ -0,0 +1,290 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use skin_utils::{
+    alfalfa::{alfalfa_capacity, AlfalfaCapacityReport},
+    png::{encode_png, PngEncodeOptions},
+};
+
+use crate::{errors::*, logic, model::*};
+
+/// How the donor's alfalfa entries are combined with the ones already in the target skin.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MergePolicy {
+    /// The target ends up with exactly the donor's entries.
+    #[default]
+    ReplaceAll,
+    /// Only donor entries the target doesn't have yet are added.
+    KeepTarget,
+    /// Entries from both skins are kept, with the donor winning where both have the same key.
+    PreferDonor,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TransplantOptions {
+    pub policy: MergePolicy,
+    pub png: PngEncodeOptions,
+}
+
+/// Where each entry of the merged alfalfa data came from. All lists are sorted by key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeReport {
+    pub policy: MergePolicy,
+    pub taken_from_donor: Vec<String>,
+    pub kept_from_target: Vec<String>,
+    /// Target entries that were overwritten or removed. [`transplant_alfalfa`] doesn't read the
+    /// target under [`MergePolicy::ReplaceAll`], so this is empty there.
+    pub dropped_from_target: Vec<String>,
+    /// Donor entries that were not used because the target already had them.
+    pub skipped_from_donor: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransplantReport {
+    #[serde(flatten)]
+    pub merge: MergeReport,
+    /// How much of the target's alfalfa space the merged data takes up.
+    pub capacity: AlfalfaCapacityReport,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransplantResult {
+    #[serde(with = "serde_bytes")]
+    pub skin: Vec<u8>,
+    pub report: TransplantReport,
+}
+
+/// Combines the donor's and target's alfalfa data according to `policy`.
+pub fn merge_alfalfa_contents(
+    donor: AlfalfaContents,
+    target: AlfalfaContents,
+    policy: MergePolicy,
+) -> (AlfalfaContents, MergeReport) {
+    let mut report = MergeReport {
+        policy,
+        taken_from_donor: Vec::new(),
+        kept_from_target: Vec::new(),
+        dropped_from_target: Vec::new(),
+        skipped_from_donor: Vec::new(),
+    };
+
+    let mut merged = match policy {
+        MergePolicy::ReplaceAll => {
+            report.dropped_from_target.extend(target.data.into_keys());
+
+            AlfalfaContents {
+                version: donor.version,
+                data: AlfalfaDataMap::new(),
+            }
+        }
+        MergePolicy::KeepTarget | MergePolicy::PreferDonor => target,
+    };
+
+    if policy == MergePolicy::PreferDonor {
+        merged.version = donor.version;
+    }
+
+    for (key, entry) in donor.data {
+        let in_target = merged.data.contains_key(&key);
+
+        if in_target && policy == MergePolicy::KeepTarget {
+            report.skipped_from_donor.push(key);
+            continue;
+        }
+
+        if in_target {
+            report.dropped_from_target.push(key.clone());
+        }
+
+        report.taken_from_donor.push(key.clone());
+        merged.data.insert(key, entry);
+    }
+
+    report.kept_from_target = merged
+        .data
+        .keys()
+        .filter(|key| !report.taken_from_donor.contains(key))
+        .cloned()
+        .collect();
+
+    report.taken_from_donor.sort();
+    report.kept_from_target.sort();
+    report.dropped_from_target.sort();
+    report.skipped_from_donor.sort();
+
+    (merged, report)
+}
+
+/// Merges the donor's alfalfa data into `target` and writes it, checking first that it fits.
+pub fn transplant_alfalfa(
+    donor: &RgbaImage,
+    target: &mut RgbaImage,
+    policy: MergePolicy,
+) -> Result<TransplantReport> {
+    let donor_contents = logic::read_alfalfa_contents(donor)?;
+    // Replacing everything doesn't use the target's entries, so a target with corrupt or
+    // painted-over alfalfa can still be written to.
+    let target_contents = match policy {
+        MergePolicy::ReplaceAll => AlfalfaContents::default(),
+        MergePolicy::KeepTarget | MergePolicy::PreferDonor => logic::read_alfalfa_contents(target)?,
+    };
+
+    let (merged, merge) = merge_alfalfa_contents(donor_contents, target_contents, policy);
+
+    let alfalfa = logic::alfalfa_from_contents(merged)?;
+    let capacity = alfalfa_capacity(target, &alfalfa);
+
+    if !capacity.fits {
+        return Err(AlfalfaInspectorError::AlfalfaTooLarge {
+            overflow: capacity.overflow,
+        });
+    }
+
+    ears_rs::alfalfa::write_alfalfa(&alfalfa, target)?;
+
+    Ok(TransplantReport { merge, capacity })
+}
+
+/// Like [`transplant_alfalfa`], but working on skin files.
+pub fn transplant_alfalfa_bytes(
+    donor_bytes: &[u8],
+    target_bytes: &[u8],
+    options: &TransplantOptions,
+) -> Result<TransplantResult> {
+    let donor = image::load_from_memory(donor_bytes)?.into_rgba8();
+    let mut target = image::load_from_memory(target_bytes)?.into_rgba8();
+
+    let report = transplant_alfalfa(&donor, &mut target, options.policy)?;
+    let skin = encode_png(&target, Some(target_bytes), &options.png)?;
+
+    Ok(TransplantResult { skin, report })
+}
+
+#[cfg(test)]
+mod tests {
+    use ears_rs::alfalfa::{read_alfalfa, write_alfalfa, AlfalfaData, AlfalfaDataKey};
+    use image::Rgba;
+
+    use super::*;
+    use crate::salvage::write_stream_byte;
+
+    fn contents(version: u8, entries: &[(&str, &[u8])]) -> AlfalfaContents {
+        AlfalfaContents {
+            version,
+            data: entries
+                .iter()
+                .map(|&(key, data)| (key.to_owned(), AlfalfaEntryData::Binary(data.to_vec())))
+                .collect(),
+        }
+    }
+
+    fn merge(policy: MergePolicy) -> (AlfalfaContents, MergeReport) {
+        let donor = contents(2, &[("shared", b"donor"), ("donor", b"d")]);
+        let target = contents(1, &[("shared", b"target"), ("target", b"t")]);
+
+        merge_alfalfa_contents(donor, target, policy)
+    }
+
+    fn keys(list: &[&str]) -> Vec<String> {
+        list.iter().map(|&key| key.to_owned()).collect()
+    }
+
+    #[test]
+    fn replace_all_keeps_only_donor_entries() {
+        let (merged, report) = merge(MergePolicy::ReplaceAll);
+
+        assert_eq!(
+            merged,
+            contents(2, &[("shared", b"donor"), ("donor", b"d")])
+        );
+        assert_eq!(report.taken_from_donor, keys(&["donor", "shared"]));
+        assert_eq!(report.kept_from_target, keys(&[]));
+        assert_eq!(report.dropped_from_target, keys(&["shared", "target"]));
+        assert_eq!(report.skipped_from_donor, keys(&[]));
+    }
+
+    #[test]
+    fn keep_target_only_adds_missing_entries() {
+        let (merged, report) = merge(MergePolicy::KeepTarget);
+
+        assert_eq!(
+            merged,
+            contents(
+                1,
+                &[("shared", b"target"), ("target", b"t"), ("donor", b"d")]
+            )
+        );
+        assert_eq!(report.taken_from_donor, keys(&["donor"]));
+        assert_eq!(report.kept_from_target, keys(&["shared", "target"]));
+        assert_eq!(report.dropped_from_target, keys(&[]));
+        assert_eq!(report.skipped_from_donor, keys(&["shared"]));
+    }
+
+    #[test]
+    fn prefer_donor_overwrites_shared_entries() {
+        let (merged, report) = merge(MergePolicy::PreferDonor);
+
+        assert_eq!(
+            merged,
+            contents(
+                2,
+                &[("shared", b"donor"), ("target", b"t"), ("donor", b"d")]
+            )
+        );
+        assert_eq!(report.taken_from_donor, keys(&["donor", "shared"]));
+        assert_eq!(report.kept_from_target, keys(&["target"]));
+        assert_eq!(report.dropped_from_target, keys(&["shared"]));
+        assert_eq!(report.skipped_from_donor, keys(&[]));
+    }
+
+    fn skin_with(entries: &[(&'static str, Vec<u8>)]) -> RgbaImage {
+        let mut skin = RgbaImage::from_pixel(64, 64, Rgba([0x40, 0x80, 0xC0, 0xFF]));
+
+        let mut alfalfa = AlfalfaData::new();
+        for (key, data) in entries {
+            alfalfa.set_data(AlfalfaDataKey::Custom(key), data.clone());
+        }
+        write_alfalfa(&alfalfa, &mut skin).unwrap();
+
+        skin
+    }
+
+    #[test]
+    fn merged_data_that_does_not_fit_leaves_the_target_alone() {
+        let donor = skin_with(&[("donor", vec![1; 1000])]);
+        let mut target = skin_with(&[("target", vec![2; 1000])]);
+        let original = target.clone();
+
+        let result = transplant_alfalfa(&donor, &mut target, MergePolicy::PreferDonor);
+
+        assert!(
+            matches!(result, Err(AlfalfaInspectorError::AlfalfaTooLarge { overflow }) if overflow > 0),
+            "{result:?}"
+        );
+        assert_eq!(target, original);
+    }
+
+    #[test]
+    fn corrupt_targets_can_still_be_replaced() {
+        let donor = skin_with(&[("donor", b"d".to_vec())]);
+        let mut target = skin_with(&[("target", b"t".to_vec())]);
+
+        // Make the length of the target's only entry point far past the end of the stream. It
+        // follows the magic, the version and the zero-terminated key.
+        write_stream_byte(&mut target, 4 + 1 + "target".len() + 1, 0xFF);
+        assert!(logic::read_alfalfa_contents(&target).is_err());
+
+        assert!(transplant_alfalfa(&donor, &mut target.clone(), MergePolicy::KeepTarget).is_err());
+
+        let report = transplant_alfalfa(&donor, &mut target, MergePolicy::ReplaceAll).unwrap();
+
+        assert_eq!(report.merge.taken_from_donor, keys(&["donor"]));
+        assert_eq!(
+            read_alfalfa(&target).unwrap().unwrap().get_data_raw(),
+            read_alfalfa(&donor).unwrap().unwrap().get_data_raw()
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use ears_rs::alfalfa::utils::EraseRegion;
+use nmsr_player_parts::{
+    model::PlayerModel,
+    parts::{
+        part::Part,
+        provider::{PartsProvider, PlayerPartProviderContext, PlayerPartsProvider},
+        uv::FaceUv,
+    },
+    types::PlayerBodyPartType,
+};
+
+use crate::{
+    errors::*,
+    models::{WasmBodyPart, WasmPartFace, WasmPartFaceSelector, WasmRegionLabel},
+};
+
+/// Returns the skin area used by `face` of the vanilla `part`.
+///
+/// Arms are 3 pixels wide instead of 4 when `slim` is set, so their faces differ between models.
+pub fn region_for_part_face(
+    part: WasmBodyPart,
+    face: WasmPartFace,
+    slim: bool,
+) -> Result<EraseRegion> {
+    let context = part_context(slim);
+
+    find_face_uv(&context, part, face)
+        .map(face_uv_to_region)
+        .ok_or(RegionEraserError::UnknownPartFace { part, face })
+}
+
+/// Lists every vanilla part face that each of `regions` overlaps.
+pub fn label_regions(regions: &[EraseRegion], slim: bool) -> Vec<WasmRegionLabel> {
+    let context = part_context(slim);
+
+    let faces: Vec<(WasmPartFaceSelector, EraseRegion)> = WasmBodyPart::ALL
+        .iter()
+        .flat_map(|&part| WasmPartFace::ALL.iter().map(move |&face| (part, face)))
+        .filter_map(|(part, face)| {
+            let region = face_uv_to_region(find_face_uv(&context, part, face)?);
+
+            Some((WasmPartFaceSelector { part, face }, region))
+        })
+        .collect();
+
+    regions
+        .iter()
+        .map(|region| WasmRegionLabel {
+            region: (*region).into(),
+            faces: faces
+                .iter()
+                .filter(|(_, face_region)| regions_overlap(region, face_region))
+                .map(|(selector, _)| *selector)
+                .collect(),
+        })
+        .collect()
+}
+
+fn part_context(slim: bool) -> PlayerPartProviderContext<()> {
+    PlayerPartProviderContext {
+        model: if slim {
+            PlayerModel::Alex
+        } else {
+            PlayerModel::Steve
+        },
+        has_hat_layer: true,
+        has_layers: true,
+        has_deadmau5_ears: false,
+        is_flipped_upside_down: false,
+        has_cape: false,
+        arm_rotation: 0f32,
+        shadow_y_pos: None,
+        shadow_is_square: false,
+        armor_slots: None,
+        ears_features: None,
+    }
+}
+
+fn find_face_uv(
+    context: &PlayerPartProviderContext<()>,
+    part: WasmBodyPart,
+    face: WasmPartFace,
+) -> Option<FaceUv> {
+    fn find_in_part(part: &Part, face: WasmPartFace) -> Option<FaceUv> {
+        match part {
+            Part::Cube { face_uvs, .. } => Some(match face {
+                WasmPartFace::North => face_uvs.north,
+                WasmPartFace::South => face_uvs.south,
+                WasmPartFace::East => face_uvs.east,
+                WasmPartFace::West => face_uvs.west,
+                WasmPartFace::Up => face_uvs.up,
+                WasmPartFace::Down => face_uvs.down,
+            }),
+            Part::Group { parts, .. } => parts.iter().find_map(|p| find_in_part(p, face)),
+            Part::Quad { .. } => None,
+        }
+    }
+
+    let body_part: PlayerBodyPartType = part.into();
+
+    PlayerPartsProvider::Minecraft
+        .get_parts(context, body_part)
+        .iter()
+        .find_map(|p| find_in_part(p, face))
+}
+
+fn face_uv_to_region(face: FaceUv) -> EraseRegion {
+    let min_x = face.top_left.x.min(face.bottom_right.x) as u8;
+    let min_y = face.top_left.y.min(face.bottom_right.y) as u8;
+    let max_x = face.top_left.x.max(face.bottom_right.x) as u8;
+    let max_y = face.top_left.y.max(face.bottom_right.y) as u8;
+
+    EraseRegion {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x,
+        height: max_y - min_y,
+    }
+}
+
+fn regions_overlap(a: &EraseRegion, b: &EraseRegion) -> bool {
+    let (a_min_x, a_max_x) = (a.x as u32, a.x as u32 + a.width as u32);
+    let (a_min_y, a_max_y) = (a.y as u32, a.y as u32 + a.height as u32);
+    let (b_min_x, b_max_x) = (b.x as u32, b.x as u32 + b.width as u32);
+    let (b_min_y, b_max_y) = (b.y as u32, b.y as u32 + b.height as u32);
+
+    a_min_x < b_max_x && b_min_x < a_max_x && a_min_y < b_max_y && b_min_y < a_max_y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(x: u8, y: u8, width: u8, height: u8) -> EraseRegion {
+        EraseRegion {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn bounds(region: &EraseRegion) -> (u8, u8, u8, u8) {
+        (region.x, region.y, region.width, region.height)
+    }
+
+    fn part_faces(part: WasmBodyPart, slim: bool) -> Vec<EraseRegion> {
+        WasmPartFace::ALL
+            .iter()
+            .map(|&face| region_for_part_face(part, face, slim).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn every_part_face_is_on_the_skin() {
+        for slim in [false, true] {
+            for part in WasmBodyPart::ALL {
+                for face in part_faces(part, slim) {
+                    assert!(face.width > 0 && face.height > 0, "{part:?}");
+                    assert!(face.x as u32 + face.width as u32 <= 64, "{part:?}");
+                    assert!(face.y as u32 + face.height as u32 <= 64, "{part:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn faces_of_a_part_do_not_overlap() {
+        for part in WasmBodyPart::ALL {
+            let faces = part_faces(part, false);
+
+            for (index, a) in faces.iter().enumerate() {
+                for b in &faces[index + 1..] {
+                    assert!(!regions_overlap(a, b), "{part:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn faces_are_found_by_part_and_direction() {
+        let top = region_for_part_face(WasmBodyPart::Head, WasmPartFace::Up, false).unwrap();
+
+        assert_eq!(bounds(&top), (8, 0, 8, 8));
+    }
+
+    #[test]
+    fn slim_arms_are_narrower() {
+        for arm in [
+            WasmBodyPart::LeftArm,
+            WasmBodyPart::RightArm,
+            WasmBodyPart::LeftArmLayer,
+            WasmBodyPart::RightArmLayer,
+        ] {
+            let classic = region_for_part_face(arm, WasmPartFace::Up, false).unwrap();
+            let slim = region_for_part_face(arm, WasmPartFace::Up, true).unwrap();
+
+            assert_eq!((classic.width, slim.width), (4, 3), "{arm:?}");
+        }
+
+        for leg in [WasmBodyPart::LeftLeg, WasmBodyPart::RightLeg] {
+            let classic = region_for_part_face(leg, WasmPartFace::Up, false).unwrap();
+            let slim = region_for_part_face(leg, WasmPartFace::Up, true).unwrap();
+
+            assert_eq!(bounds(&classic), bounds(&slim), "{leg:?}");
+        }
+    }
+
+    #[test]
+    fn regions_are_labelled_with_the_faces_they_touch() {
+        let labels = label_regions(&[region(8, 0, 8, 8), region(0, 0, 8, 8)], false);
+
+        assert_eq!(
+            labels[0].faces,
+            vec![WasmPartFaceSelector {
+                part: WasmBodyPart::Head,
+                face: WasmPartFace::Up,
+            }]
+        );
+
+        // The corner left of the head's top isn't used by any part.
+        assert_eq!(labels[1].faces, vec![]);
+    }
+
+    #[test]
+    fn touching_regions_do_not_overlap() {
+        let a = region(4, 4, 4, 4);
+
+        assert!(regions_overlap(&a, &region(7, 7, 1, 1)));
+        assert!(regions_overlap(&a, &region(0, 0, 64, 64)));
+        assert!(!regions_overlap(&a, &region(8, 4, 4, 4)));
+        assert!(!regions_overlap(&a, &region(4, 8, 4, 4)));
+        assert!(!regions_overlap(&a, &region(0, 0, 4, 4)));
+    }
+}
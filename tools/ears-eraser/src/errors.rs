@@ -1,12 +1,160 @@
+use js_sys::Reflect;
+use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
+use wasm_bindgen::JsValue;
+
+use crate::models::{WasmBodyPart, WasmPartFace};
 
 #[derive(Error, Debug)]
 pub enum RegionEraserError {
     #[error("Ears error: {0}")]
     EarsError(#[from] ears_rs::utils::errors::EarsError),
-    
+
     #[error("Image error: {0}")]
     ImageError(#[from] image::error::ImageError),
+
+    #[error("The provided file is not a PNG image")]
+    NotPng,
+
+    #[error("Expected a 64x64 image, got {width}x{height}")]
+    InvalidDimensions { width: u32, height: u32 },
+
+    #[error("The alfalfa data does not fit in this skin ({size} bytes, but only {capacity} fit)")]
+    AlfalfaTooLarge { size: usize, capacity: usize },
+
+    #[error("Too many erase regions ({count}) to fit in this skin")]
+    TooManyRegions { count: usize },
+
+    #[error("The skin contains corrupt alfalfa data: {0}")]
+    CorruptAlfalfa(#[source] ears_rs::utils::errors::EarsError),
+
+    #[error("Region at ({x}, {y}) starts outside of the 64x64 skin area")]
+    RegionOutOfBounds { x: u8, y: u8 },
+
+    #[error("Unable to find the {face:?} face of {part:?}")]
+    UnknownPartFace {
+        part: WasmBodyPart,
+        face: WasmPartFace,
+    },
+
+    #[error(
+        "Invalid preview scale {scale} (must be between 1 and {max})",
+        max = crate::preview::MAX_PREVIEW_SCALE
+    )]
+    InvalidScale { scale: u32 },
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(#[source] serde_wasm_bindgen::Error),
+
+    #[error("Unable to serialize the result: {0}")]
+    SerializationError(#[source] serde_wasm_bindgen::Error),
+}
+
+/// Structured fields attached to an error, so that frontends don't have to parse the message.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorDetails {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub part: Option<WasmBodyPart>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub face: Option<WasmPartFace>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<u32>,
+}
+
+impl RegionEraserError {
+    /// A stable identifier for this kind of error. These never change between releases.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RegionEraserError::EarsError(_) => "EARS_ERROR",
+            RegionEraserError::ImageError(_) => "IMAGE_ERROR",
+            RegionEraserError::NotPng => "NOT_PNG",
+            RegionEraserError::InvalidDimensions { .. } => "INVALID_DIMENSIONS",
+            RegionEraserError::AlfalfaTooLarge { .. } => "ALFALFA_TOO_LARGE",
+            RegionEraserError::TooManyRegions { .. } => "TOO_MANY_REGIONS",
+            RegionEraserError::CorruptAlfalfa(_) => "CORRUPT_ALFALFA",
+            RegionEraserError::RegionOutOfBounds { .. } => "REGION_OUT_OF_BOUNDS",
+            RegionEraserError::UnknownPartFace { .. } => "UNKNOWN_PART_FACE",
+            RegionEraserError::InvalidScale { .. } => "INVALID_SCALE",
+            RegionEraserError::InvalidInput(_) => "INVALID_INPUT",
+            RegionEraserError::SerializationError(_) => "SERIALIZATION_ERROR",
+        }
+    }
+
+    pub fn details(&self) -> ErrorDetails {
+        match *self {
+            RegionEraserError::InvalidDimensions { width, height } => ErrorDetails {
+                width: Some(width),
+                height: Some(height),
+                ..Default::default()
+            },
+            RegionEraserError::AlfalfaTooLarge { size, capacity } => ErrorDetails {
+                size: Some(size),
+                capacity: Some(capacity),
+                ..Default::default()
+            },
+            RegionEraserError::TooManyRegions { count } => ErrorDetails {
+                region_count: Some(count),
+                ..Default::default()
+            },
+            RegionEraserError::RegionOutOfBounds { x, y } => ErrorDetails {
+                x: Some(x),
+                y: Some(y),
+                ..Default::default()
+            },
+            RegionEraserError::UnknownPartFace { part, face } => ErrorDetails {
+                part: Some(part),
+                face: Some(face),
+                ..Default::default()
+            },
+            RegionEraserError::InvalidScale { scale } => ErrorDetails {
+                scale: Some(scale),
+                ..Default::default()
+            },
+            _ => ErrorDetails::default(),
+        }
+    }
 }
 
-pub(crate) type Result<T> = std::result::Result<T, RegionEraserError>;
\ No newline at end of file
+/// Errors reach JavaScript as an `Error` with extra `code` and `details` properties.
+impl From<RegionEraserError> for JsValue {
+    fn from(error: RegionEraserError) -> Self {
+        let js_error = js_sys::Error::new(&error.to_string());
+        js_error.set_name("RegionEraserError");
+
+        let _ = Reflect::set(&js_error, &"code".into(), &error.code().into());
+
+        if let Ok(details) = serde_wasm_bindgen::to_value(&error.details()) {
+            let _ = Reflect::set(&js_error, &"details".into(), &details);
+        }
+
+        js_error.into()
+    }
+}
+
+pub(crate) type Result<T> = std::result::Result<T, RegionEraserError>;
+
+/// Reads a value passed in from JavaScript, reporting failures as invalid input.
+pub(crate) fn from_js_value<T: DeserializeOwned>(value: JsValue) -> Result<T> {
+    serde_wasm_bindgen::from_value(value).map_err(RegionEraserError::InvalidInput)
+}
+
+/// Converts a result to hand back to JavaScript. Failures are bugs on our side rather than bad
+/// input, so they are reported as such.
+pub(crate) fn to_js_value<T: Serialize>(value: &T) -> Result<JsValue> {
+    serde_wasm_bindgen::to_value(value).map_err(RegionEraserError::SerializationError)
+}
@@ -0,0 +1,50 @@
+use image::{GenericImage, RgbaImage};
+
+/// Whether `image` uses the pre-1.8 64x32 skin layout.
+pub fn is_legacy_skin(image: &RgbaImage) -> bool {
+    image.width() == 64 && image.height() == 32
+}
+
+/// Converts a 64x32 skin into the 64x64 layout, the same way vanilla Minecraft does.
+///
+/// Legacy skins only have a right arm and a right leg, so the left limbs are created by mirroring
+/// every face of them. The new overlay layers are left fully transparent.
+pub fn upgrade_legacy_skin(image: &RgbaImage) -> RgbaImage {
+    let mut result = RgbaImage::new(64, 64);
+    result
+        .copy_from(image, 0, 0)
+        .expect("a 64x32 image always fits in a 64x64 one");
+
+    // (source x, source y, x offset, y offset, width, height), taken from vanilla's skin processing.
+    const MIRRORED_FACES: [(u32, u32, i32, i32, u32, u32); 12] = [
+        // Leg: top, bottom, then the four sides
+        (4, 16, 16, 32, 4, 4),
+        (8, 16, 16, 32, 4, 4),
+        (0, 20, 24, 32, 4, 12),
+        (4, 20, 16, 32, 4, 12),
+        (8, 20, 8, 32, 4, 12),
+        (12, 20, 16, 32, 4, 12),
+        // Arm: top, bottom, then the four sides
+        (44, 16, -8, 32, 4, 4),
+        (48, 16, -8, 32, 4, 4),
+        (40, 20, 0, 32, 4, 12),
+        (44, 20, -8, 32, 4, 12),
+        (48, 20, -16, 32, 4, 12),
+        (52, 20, -8, 32, 4, 12),
+    ];
+
+    for (x, y, offset_x, offset_y, width, height) in MIRRORED_FACES {
+        for dy in 0..height {
+            for dx in 0..width {
+                let pixel = *image.get_pixel(x + dx, y + dy);
+
+                let target_x = (x as i32 + offset_x) as u32 + (width - 1 - dx);
+                let target_y = (y as i32 + offset_y) as u32 + dy;
+
+                result.put_pixel(target_x, target_y, pixel);
+            }
+        }
+    }
+
+    result
+}
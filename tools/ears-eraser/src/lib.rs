@@ -1,28 +1,57 @@
-use crate::models::EarsImageWorkspace;
-use js_utils::JsResult;
+use crate::{
+    errors::{from_js_value, to_js_value, Result},
+    models::{EarsImageWorkspace, WasmEraseRegion, WasmPartFaceSelector},
+};
+#[cfg(target_arch = "wasm32")]
 use lol_alloc::{AssumeSingleThreaded, FreeListAllocator};
+use skin_utils::png::PngEncodeOptions;
 use wasm_bindgen::prelude::*;
 
 extern crate alloc;
 
+pub mod body_parts;
 pub mod errors;
-mod logic;
-mod models;
+pub mod legacy;
+pub mod logic;
+pub mod mask;
+pub mod models;
+pub mod normalize;
+pub mod preview;
 
 // SAFETY: This application is single threaded, so using AssumeSingleThreaded is allowed.
+#[cfg(target_arch = "wasm32")]
 #[global_allocator]
 static ALLOCATOR: AssumeSingleThreaded<FreeListAllocator> =
     unsafe { AssumeSingleThreaded::new(FreeListAllocator::new()) };
 
 #[wasm_bindgen]
-pub fn decode_ears_image(skin_bytes: &[u8]) -> JsResult<EarsImageWorkspace> {
+pub fn decode_ears_image(skin_bytes: &[u8]) -> Result<EarsImageWorkspace> {
     Ok(logic::decode_ears_image(skin_bytes)?.into())
 }
 
 #[wasm_bindgen]
-pub fn encode_ears_image(
+pub fn encode_ears_image(skin_bytes: &[u8], workspace: &mut EarsImageWorkspace) -> Result<Vec<u8>> {
+    logic::encode_ears_image(skin_bytes, workspace)
+}
+
+/// Like [`encode_ears_image`], but with control over the PNG compression and filter,
+/// and over whether the metadata of the original file is kept.
+#[wasm_bindgen]
+pub fn encode_ears_image_with_options(
     skin_bytes: &[u8],
     workspace: &mut EarsImageWorkspace,
-) -> JsResult<Vec<u8>> {
-    Ok(logic::encode_ears_image(skin_bytes, workspace)?)
+    options: JsValue,
+) -> Result<Vec<u8>> {
+    let options: Option<PngEncodeOptions> = from_js_value(options)?;
+
+    logic::encode_ears_image_with_options(skin_bytes, workspace, &options.unwrap_or_default())
+}
+
+#[wasm_bindgen]
+pub fn get_part_face_region(selector: JsValue, slim: bool) -> Result<JsValue> {
+    let selector: WasmPartFaceSelector = from_js_value(selector)?;
+    let region: WasmEraseRegion =
+        body_parts::region_for_part_face(selector.part, selector.face, slim)?.into();
+
+    to_js_value(&region)
 }
@@ -1,45 +1,272 @@
 use std::io::Cursor;
 
-use ears_rs::alfalfa::{utils::EraseRegionsProvider, AlfalfaData};
+use ears_rs::alfalfa::{
+    utils::{EraseRegion, EraseRegionsProvider},
+    AlfalfaData, AlfalfaDataKey,
+};
 
-use crate::{errors::*, models::EarsImageWorkspace};
+use crate::{
+    errors::*,
+    legacy,
+    mask::EraseMask,
+    models::{EarsImageWorkspace, NormalizationReport},
+    normalize, preview,
+};
 pub use ears_rs;
-use image::ImageFormat;
+use image::{ImageFormat, RgbaImage};
+use skin_utils::{alfalfa::AlfalfaCapacityReport, png::PngEncodeOptions};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+pub(crate) fn load_png(bytes: &[u8]) -> Result<RgbaImage> {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(RegionEraserError::NotPng);
+    }
+
+    Ok(image::load_from_memory_with_format(bytes, ImageFormat::Png)?.into_rgba8())
+}
+
+/// Loads a 64x64 skin, upgrading legacy 64x32 skins first. Also returns whether that happened.
+pub(crate) fn load_skin(bytes: &[u8]) -> Result<(RgbaImage, bool)> {
+    let image = load_png(bytes)?;
+
+    if legacy::is_legacy_skin(&image) {
+        return Ok((legacy::upgrade_legacy_skin(&image), true));
+    }
+
+    if image.width() != 64 || image.height() != 64 {
+        return Err(RegionEraserError::InvalidDimensions {
+            width: image.width(),
+            height: image.height(),
+        });
+    }
+
+    Ok((image, false))
+}
+
+/// Writes `data` into `image`, telling apart a payload that is too large because of its erase
+/// regions from one that wouldn't fit regardless.
+pub(crate) fn write_alfalfa(data: &AlfalfaData, image: &mut RgbaImage) -> Result<()> {
+    if ears_rs::alfalfa::write_alfalfa(data, image).is_ok() {
+        return Ok(());
+    }
+
+    let region_count = data
+        .get_erase_regions()
+        .ok()
+        .flatten()
+        .map_or(0, |regions| regions.len());
+
+    if region_count > 0 {
+        let mut without_regions = data.clone();
+        without_regions.remove_data(AlfalfaDataKey::Erase);
+
+        if ears_rs::alfalfa::write_alfalfa(&without_regions, &mut image.clone()).is_ok() {
+            return Err(RegionEraserError::TooManyRegions {
+                count: region_count,
+            });
+        }
+    }
+
+    let capacity = skin_utils::alfalfa::alfalfa_capacity(image, data);
+
+    Err(RegionEraserError::AlfalfaTooLarge {
+        size: capacity.total_capacity + capacity.overflow,
+        capacity: capacity.total_capacity,
+    })
+}
 
 #[inline(never)]
 pub fn decode_ears_image(skin_bytes: &[u8]) -> Result<EarsImageWorkspace> {
-    let image = image::load_from_memory_with_format(skin_bytes, ImageFormat::Png)?.into_rgba8();
+    let (image, upgraded_from_legacy) = load_skin(skin_bytes)?;
 
-    let data = ears_rs::alfalfa::read_alfalfa(&image)?;
+    let data = ears_rs::alfalfa::read_alfalfa(&image).map_err(RegionEraserError::CorruptAlfalfa)?;
     let alfalfa = data.unwrap_or_else(|| AlfalfaData::new());
 
     let regions = alfalfa
-        .get_erase_regions()?
+        .get_erase_regions()
+        .map_err(RegionEraserError::CorruptAlfalfa)?
         .unwrap_or_else(|| vec![])
         .into_iter()
         .map(|r| r.into())
         .collect();
 
-    Ok(EarsImageWorkspace { alfalfa, regions })
+    Ok(EarsImageWorkspace {
+        alfalfa,
+        regions,
+        history: Default::default(),
+        upgraded_from_legacy,
+    })
 }
 
 #[inline(never)]
 pub fn encode_ears_image(skin_bytes: &[u8], workspace: &mut EarsImageWorkspace) -> Result<Vec<u8>> {
-    let mut image = image::load_from_memory_with_format(skin_bytes, ImageFormat::Png)?.into_rgba8();
+    encode_ears_image_with_options(skin_bytes, workspace, &PngEncodeOptions::default())
+}
+
+#[inline(never)]
+pub fn encode_ears_image_with_options(
+    skin_bytes: &[u8],
+    workspace: &mut EarsImageWorkspace,
+    options: &PngEncodeOptions,
+) -> Result<Vec<u8>> {
+    let (mut image, _) = load_skin(skin_bytes)?;
 
     let data = &mut workspace.alfalfa;
     data.set_erase_regions(&workspace.regions)?;
 
-    ears_rs::alfalfa::write_alfalfa(&data, &mut image)?;
+    write_alfalfa(data, &mut image)?;
 
     if data.is_empty() {
         ears_rs::utils::strip_alpha(&mut image);
     }
 
+    Ok(skin_utils::png::encode_png(
+        &image,
+        Some(skin_bytes),
+        options,
+    )?)
+}
+
+/// Finds the regions that were erased between two versions of a skin. Legacy 64x32 skins are
+/// upgraded first, so either version may use the old layout.
+#[inline(never)]
+pub fn regions_from_erased_skin(
+    original_bytes: &[u8],
+    erased_bytes: &[u8],
+) -> Result<Vec<EraseRegion>> {
+    let (original, _) = load_skin(original_bytes)?;
+    let (erased, _) = load_skin(erased_bytes)?;
+
+    Ok(EraseMask::from_erased_skin(&original, &erased)?.to_regions())
+}
+
+/// Finds the regions painted in a mask image. A 64x32 mask is upgraded like a legacy skin, so
+/// what is painted on the right limbs is erased from the mirrored left limbs as well.
+#[inline(never)]
+pub fn regions_from_mask(mask_bytes: &[u8]) -> Result<Vec<EraseRegion>> {
+    let (mask, _) = load_skin(mask_bytes)?;
+
+    Ok(EraseMask::from_mask_image(&mask)?.to_regions())
+}
+
+#[inline(never)]
+pub fn normalize_workspace_regions(
+    workspace: &mut EarsImageWorkspace,
+) -> Result<NormalizationReport> {
+    let (regions, report) = normalize::normalize_regions(&workspace.regions)?;
+    workspace.set_erase_regions(regions);
+
+    Ok(report)
+}
+
+#[inline(never)]
+pub fn render_preview(
+    skin_bytes: &[u8],
+    workspace: &EarsImageWorkspace,
+    scale: u32,
+    overlay: bool,
+) -> Result<Vec<u8>> {
+    let (image, _) = load_skin(skin_bytes)?;
+
+    let erased = preview::apply_erase_regions(&image, &workspace.alfalfa, &workspace.regions)?;
+
+    let outlined_regions: &[EraseRegion] = if overlay { &workspace.regions } else { &[] };
+    let preview = preview::draw_region_outlines(&erased, outlined_regions, scale)?;
+
     let mut bytes = Vec::new();
     {
-        image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+        preview.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
     }
 
     Ok(bytes)
 }
+
+#[inline(never)]
+pub fn alfalfa_capacity(
+    skin_bytes: &[u8],
+    workspace: &EarsImageWorkspace,
+) -> Result<AlfalfaCapacityReport> {
+    let (image, _) = load_skin(skin_bytes)?;
+
+    let mut data = workspace.alfalfa.clone();
+    data.set_erase_regions(&workspace.regions)?;
+
+    Ok(skin_utils::alfalfa::alfalfa_capacity(&image, &data))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn png(image: &RgbaImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+
+        bytes
+    }
+
+    fn bounds(regions: &[EraseRegion]) -> Vec<(u8, u8, u8, u8)> {
+        regions
+            .iter()
+            .map(|region| (region.x, region.y, region.width, region.height))
+            .collect()
+    }
+
+    #[test]
+    fn oversized_alfalfa_reports_its_size_and_the_capacity() {
+        let mut data = AlfalfaData::new();
+        data.set_data(AlfalfaDataKey::Wings, vec![0xA5; 4096]);
+
+        let error = write_alfalfa(&data, &mut RgbaImage::new(64, 64)).unwrap_err();
+
+        let RegionEraserError::AlfalfaTooLarge { size, capacity } = error else {
+            panic!("unexpected error {error:?}");
+        };
+        assert!(capacity > 0 && capacity < 4096);
+        assert_eq!(size, 4096);
+    }
+
+    #[test]
+    fn legacy_skins_can_be_compared() {
+        let original = RgbaImage::from_pixel(64, 32, Rgba([0xFF; 4]));
+        let mut erased = original.clone();
+        erased.put_pixel(8, 8, Rgba([0; 4]));
+
+        let regions = regions_from_erased_skin(&png(&original), &png(&erased)).unwrap();
+
+        assert_eq!(bounds(&regions), vec![(8, 8, 1, 1)]);
+    }
+
+    #[test]
+    fn legacy_masks_are_mirrored_onto_the_left_limbs() {
+        let mut mask = RgbaImage::new(64, 32);
+        // The front of the right leg
+        for y in 20..32 {
+            for x in 4..8 {
+                mask.put_pixel(x, y, Rgba([0xFF; 4]));
+            }
+        }
+
+        let regions = regions_from_mask(&png(&mask)).unwrap();
+
+        assert_eq!(bounds(&regions), vec![(4, 20, 4, 12), (20, 52, 4, 12)]);
+    }
+
+    #[test]
+    fn masks_of_other_sizes_are_refused() {
+        let error = regions_from_mask(&png(&RgbaImage::new(32, 32))).unwrap_err();
+
+        assert!(matches!(
+            error,
+            RegionEraserError::InvalidDimensions {
+                width: 32,
+                height: 32
+            }
+        ));
+    }
+}
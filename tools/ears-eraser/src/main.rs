@@ -0,0 +1,140 @@
+use std::{error::Error, path::PathBuf, process::ExitCode};
+
+use ears_eraser::{errors::RegionEraserError, logic, models::WasmEraseRegion};
+use ears_rs::alfalfa::utils::EraseRegion;
+
+const USAGE: &str = "\
+Usage:
+    ears-eraser list <skin.png>
+    ears-eraser add <skin.png> <x> <y> <width> <height> [-o <out.png>]
+    ears-eraser remove <skin.png> <index> [-o <out.png>]
+    ears-eraser clear <skin.png> [-o <out.png>]
+    ears-eraser normalize <skin.png> [-o <out.png>]
+
+Commands that change the regions write the re-encoded skin back to <skin.png>,
+or to <out.png> when given. Every command prints the resulting regions as JSON.
+`normalize` also prints a report of what it changed to stderr.";
+
+enum Command {
+    List,
+    Add(EraseRegion),
+    Remove(usize),
+    Clear,
+    Normalize,
+}
+
+struct Args {
+    command: Command,
+    input: PathBuf,
+    output: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Option<Args> {
+    let command = args.next()?;
+    let input = PathBuf::from(args.next()?);
+
+    let mut positional = Vec::new();
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(args.next()?)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let command = match (command.as_str(), positional.as_slice()) {
+        ("list", []) => Command::List,
+        ("add", [x, y, width, height]) => Command::Add(EraseRegion {
+            x: x.parse().ok()?,
+            y: y.parse().ok()?,
+            width: width.parse().ok()?,
+            height: height.parse().ok()?,
+        }),
+        ("remove", [index]) => Command::Remove(index.parse().ok()?),
+        ("clear", []) => Command::Clear,
+        ("normalize", []) => Command::Normalize,
+        _ => return None,
+    };
+
+    Some(Args {
+        command,
+        input,
+        output,
+    })
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let skin_bytes = std::fs::read(&args.input)?;
+    let mut workspace = logic::decode_ears_image(&skin_bytes)?;
+
+    if workspace.was_upgraded_from_legacy() {
+        eprintln!("Note: upgraded legacy 64x32 skin to the 64x64 layout");
+    }
+
+    let mut regions = workspace.regions().to_vec();
+
+    let changed = match args.command {
+        Command::List => false,
+        Command::Normalize => {
+            let report = logic::normalize_workspace_regions(&mut workspace)?;
+            eprintln!("{}", serde_json::to_string_pretty(&report)?);
+
+            regions = workspace.regions().to_vec();
+            true
+        }
+        Command::Add(region) => {
+            regions.push(region);
+            true
+        }
+        Command::Remove(index) => {
+            if index >= regions.len() {
+                return Err(format!(
+                    "Region index {index} is out of bounds (skin has {} regions)",
+                    regions.len()
+                )
+                .into());
+            }
+
+            regions.remove(index);
+            true
+        }
+        Command::Clear => {
+            regions.clear();
+            true
+        }
+    };
+
+    if changed {
+        workspace.set_erase_regions(regions);
+
+        let bytes = logic::encode_ears_image(&skin_bytes, &mut workspace)?;
+        std::fs::write(args.output.as_ref().unwrap_or(&args.input), bytes)?;
+    }
+
+    let wasm_regions: Vec<WasmEraseRegion> =
+        workspace.regions().iter().map(|r| (*r).into()).collect();
+
+    println!("{}", serde_json::to_string_pretty(&wasm_regions)?);
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let Some(args) = parse_args(std::env::args().skip(1)) else {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            match err.downcast_ref::<RegionEraserError>() {
+                Some(eraser_error) => eprintln!("Error [{}]: {err}", eraser_error.code()),
+                None => eprintln!("Error: {err}"),
+            }
+
+            ExitCode::FAILURE
+        }
+    }
+}
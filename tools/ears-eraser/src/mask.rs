@@ -0,0 +1,257 @@
+use ears_rs::alfalfa::utils::EraseRegion;
+use image::RgbaImage;
+
+use crate::errors::*;
+
+/// Width and height of the skin area erase regions can address.
+pub const SKIN_SIZE: u32 = 64;
+
+/// Largest width or height a single erase region can have, as Ears stores `size - 1` in 5 bits.
+pub const MAX_REGION_SIZE: u8 = 32;
+
+/// A per-pixel selection of the 64x64 skin area that should be erased.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EraseMask {
+    pixels: [bool; (SKIN_SIZE * SKIN_SIZE) as usize],
+}
+
+impl Default for EraseMask {
+    fn default() -> Self {
+        Self {
+            pixels: [false; (SKIN_SIZE * SKIN_SIZE) as usize],
+        }
+    }
+}
+
+impl EraseMask {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a mask from an artist-painted mask image, where every non-transparent pixel is erased.
+    pub fn from_mask_image(mask: &RgbaImage) -> Result<Self> {
+        check_dimensions(mask)?;
+
+        let mut result = Self::new();
+        for (x, y, pixel) in mask.enumerate_pixels() {
+            result.set(x, y, pixel.0[3] != 0);
+        }
+
+        Ok(result)
+    }
+
+    /// Builds a mask from the pixels that are visible in `original` but were made transparent in `erased`.
+    pub fn from_erased_skin(original: &RgbaImage, erased: &RgbaImage) -> Result<Self> {
+        check_dimensions(original)?;
+        check_dimensions(erased)?;
+
+        let mut result = Self::new();
+        for (x, y, pixel) in original.enumerate_pixels() {
+            result.set(x, y, pixel.0[3] != 0 && erased.get_pixel(x, y).0[3] == 0);
+        }
+
+        Ok(result)
+    }
+
+    /// Builds a mask from existing regions, clipping anything that runs past the skin area.
+    pub fn from_regions(regions: &[EraseRegion]) -> Self {
+        let mut result = Self::new();
+
+        for region in regions {
+            let max_x = (region.x as u32 + region.width as u32).min(SKIN_SIZE);
+            let max_y = (region.y as u32 + region.height as u32).min(SKIN_SIZE);
+
+            for y in region.y as u32..max_y {
+                for x in region.x as u32..max_x {
+                    result.set(x, y, true);
+                }
+            }
+        }
+
+        result
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        x < SKIN_SIZE && y < SKIN_SIZE && self.pixels[(y * SKIN_SIZE + x) as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, value: bool) {
+        if x < SKIN_SIZE && y < SKIN_SIZE {
+            self.pixels[(y * SKIN_SIZE + x) as usize] = value;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.pixels.contains(&true)
+    }
+
+    pub fn count(&self) -> usize {
+        self.pixels.iter().filter(|&&p| p).count()
+    }
+
+    /// Splits the mask into rectangles that cover exactly the selected pixels.
+    ///
+    /// Rectangles are grown greedily (first to the right, then downwards) from the topmost, leftmost
+    /// pixel that is not covered yet. They may overlap each other, but never cover unselected pixels.
+    pub fn to_regions(&self) -> Vec<EraseRegion> {
+        let mut covered = Self::new();
+        let mut regions = Vec::new();
+
+        for y in 0..SKIN_SIZE {
+            for x in 0..SKIN_SIZE {
+                if !self.get(x, y) || covered.get(x, y) {
+                    continue;
+                }
+
+                let mut width = 1;
+                while width < MAX_REGION_SIZE as u32 && self.get(x + width, y) {
+                    width += 1;
+                }
+
+                let mut height = 1;
+                while height < MAX_REGION_SIZE as u32
+                    && (x..x + width).all(|rx| self.get(rx, y + height))
+                {
+                    height += 1;
+                }
+
+                for ry in y..y + height {
+                    for rx in x..x + width {
+                        covered.set(rx, ry, true);
+                    }
+                }
+
+                regions.push(EraseRegion {
+                    x: x as u8,
+                    y: y as u8,
+                    width: width as u8,
+                    height: height as u8,
+                });
+            }
+        }
+
+        regions
+    }
+}
+
+fn check_dimensions(image: &RgbaImage) -> Result<()> {
+    if image.width() != SKIN_SIZE || image.height() != SKIN_SIZE {
+        return Err(RegionEraserError::InvalidDimensions {
+            width: image.width(),
+            height: image.height(),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn bounds(regions: &[EraseRegion]) -> Vec<(u8, u8, u8, u8)> {
+        regions
+            .iter()
+            .map(|region| (region.x, region.y, region.width, region.height))
+            .collect()
+    }
+
+    fn mask_from(selected: impl Fn(u32, u32) -> bool) -> EraseMask {
+        let mut mask = EraseMask::new();
+        for y in 0..SKIN_SIZE {
+            for x in 0..SKIN_SIZE {
+                mask.set(x, y, selected(x, y));
+            }
+        }
+
+        mask
+    }
+
+    #[test]
+    fn empty_masks_have_no_regions() {
+        assert!(EraseMask::new().to_regions().is_empty());
+    }
+
+    #[test]
+    fn rectangles_become_a_single_region() {
+        let mask = mask_from(|x, y| (3..8).contains(&x) && (10..12).contains(&y));
+
+        assert_eq!(bounds(&mask.to_regions()), vec![(3, 10, 5, 2)]);
+    }
+
+    #[test]
+    fn regions_are_at_most_the_largest_size_ears_can_store() {
+        let regions = mask_from(|_, _| true).to_regions();
+
+        assert_eq!(
+            bounds(&regions),
+            vec![
+                (0, 0, 32, 32),
+                (32, 0, 32, 32),
+                (0, 32, 32, 32),
+                (32, 32, 32, 32)
+            ]
+        );
+    }
+
+    #[test]
+    fn regions_cover_exactly_the_selected_pixels() {
+        let masks = [
+            mask_from(|x, y| (x * 7 + y * 3) % 5 < 2),
+            mask_from(|x, y| x.abs_diff(32) + y.abs_diff(32) < 20),
+            mask_from(|x, y| x == 63 || y == 63),
+        ];
+
+        for mask in masks {
+            let regions = mask.to_regions();
+
+            assert!(regions.iter().all(|region| {
+                (1..=MAX_REGION_SIZE).contains(&region.width)
+                    && (1..=MAX_REGION_SIZE).contains(&region.height)
+            }));
+            assert!(EraseMask::from_regions(&regions) == mask);
+        }
+    }
+
+    #[test]
+    fn regions_past_the_skin_are_clipped() {
+        let mask = EraseMask::from_regions(&[EraseRegion {
+            x: 60,
+            y: 62,
+            width: 8,
+            height: 8,
+        }]);
+
+        assert_eq!(mask.count(), 4 * 2);
+        assert_eq!(bounds(&mask.to_regions()), vec![(60, 62, 4, 2)]);
+    }
+
+    #[test]
+    fn only_pixels_made_transparent_are_taken_from_an_erased_skin() {
+        let mut original = RgbaImage::from_pixel(SKIN_SIZE, SKIN_SIZE, Rgba([0xFF; 4]));
+        original.put_pixel(0, 0, Rgba([0; 4]));
+
+        let mut erased = original.clone();
+        erased.put_pixel(0, 0, Rgba([0; 4]));
+        erased.put_pixel(5, 6, Rgba([0xFF, 0xFF, 0xFF, 0]));
+
+        let mask = EraseMask::from_erased_skin(&original, &erased).unwrap();
+
+        assert_eq!(bounds(&mask.to_regions()), vec![(5, 6, 1, 1)]);
+    }
+
+    #[test]
+    fn mask_images_have_to_cover_the_skin() {
+        let mask = RgbaImage::new(64, 32);
+
+        assert!(matches!(
+            EraseMask::from_mask_image(&mask),
+            Err(RegionEraserError::InvalidDimensions {
+                width: 64,
+                height: 32
+            })
+        ));
+    }
+}
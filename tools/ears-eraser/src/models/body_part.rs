@@ -0,0 +1,92 @@
+use nmsr_player_parts::types::PlayerBodyPartType;
+use serde::{Deserialize, Serialize};
+
+use crate::models::WasmEraseRegion;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WasmBodyPart {
+    Head,
+    Body,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+    HeadLayer,
+    BodyLayer,
+    LeftArmLayer,
+    RightArmLayer,
+    LeftLegLayer,
+    RightLegLayer,
+}
+
+impl WasmBodyPart {
+    pub const ALL: [WasmBodyPart; 12] = [
+        WasmBodyPart::Head,
+        WasmBodyPart::Body,
+        WasmBodyPart::LeftArm,
+        WasmBodyPart::RightArm,
+        WasmBodyPart::LeftLeg,
+        WasmBodyPart::RightLeg,
+        WasmBodyPart::HeadLayer,
+        WasmBodyPart::BodyLayer,
+        WasmBodyPart::LeftArmLayer,
+        WasmBodyPart::RightArmLayer,
+        WasmBodyPart::LeftLegLayer,
+        WasmBodyPart::RightLegLayer,
+    ];
+}
+
+impl From<WasmBodyPart> for PlayerBodyPartType {
+    fn from(part: WasmBodyPart) -> Self {
+        match part {
+            WasmBodyPart::Head => PlayerBodyPartType::Head,
+            WasmBodyPart::Body => PlayerBodyPartType::Body,
+            WasmBodyPart::LeftArm => PlayerBodyPartType::LeftArm,
+            WasmBodyPart::RightArm => PlayerBodyPartType::RightArm,
+            WasmBodyPart::LeftLeg => PlayerBodyPartType::LeftLeg,
+            WasmBodyPart::RightLeg => PlayerBodyPartType::RightLeg,
+            WasmBodyPart::HeadLayer => PlayerBodyPartType::HeadLayer,
+            WasmBodyPart::BodyLayer => PlayerBodyPartType::BodyLayer,
+            WasmBodyPart::LeftArmLayer => PlayerBodyPartType::LeftArmLayer,
+            WasmBodyPart::RightArmLayer => PlayerBodyPartType::RightArmLayer,
+            WasmBodyPart::LeftLegLayer => PlayerBodyPartType::LeftLegLayer,
+            WasmBodyPart::RightLegLayer => PlayerBodyPartType::RightLegLayer,
+        }
+    }
+}
+
+/// Cube faces, named the same way as in `nmsr-player-parts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WasmPartFace {
+    North,
+    South,
+    East,
+    West,
+    Up,
+    Down,
+}
+
+impl WasmPartFace {
+    pub const ALL: [WasmPartFace; 6] = [
+        WasmPartFace::North,
+        WasmPartFace::South,
+        WasmPartFace::East,
+        WasmPartFace::West,
+        WasmPartFace::Up,
+        WasmPartFace::Down,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WasmPartFaceSelector {
+    pub part: WasmBodyPart,
+    pub face: WasmPartFace,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WasmRegionLabel {
+    pub region: WasmEraseRegion,
+    pub faces: Vec<WasmPartFaceSelector>,
+}
@@ -30,4 +30,13 @@ impl From<EraseRegion> for WasmEraseRegion {
             height: value.height,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Compares two regions field by field, as [`EraseRegion`] doesn't implement `PartialEq`.
+pub(crate) fn same_region(a: &EraseRegion, b: &EraseRegion) -> bool {
+    a.x == b.x && a.y == b.y && a.width == b.width && a.height == b.height
+}
+
+pub(crate) fn same_regions(a: &[EraseRegion], b: &[EraseRegion]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_region(a, b))
+}
@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+
+use ears_rs::alfalfa::utils::EraseRegion;
+
+use crate::models::same_regions;
+
+/// How many region edits are kept around for undoing.
+pub const MAX_HISTORY_SIZE: usize = 100;
+
+/// Bounded undo/redo stacks of region snapshots.
+#[derive(Default)]
+pub struct RegionHistory {
+    undo_stack: VecDeque<Vec<EraseRegion>>,
+    redo_stack: Vec<Vec<EraseRegion>>,
+}
+
+impl RegionHistory {
+    /// Records that the regions are about to change from `previous` to `next`.
+    pub(crate) fn record(&mut self, previous: &[EraseRegion], next: &[EraseRegion]) {
+        if same_regions(previous, next) {
+            return;
+        }
+
+        if self.undo_stack.len() == MAX_HISTORY_SIZE {
+            self.undo_stack.pop_front();
+        }
+
+        self.undo_stack.push_back(previous.to_vec());
+        self.redo_stack.clear();
+    }
+
+    /// Takes the previous snapshot, remembering `current` so that it can be redone.
+    pub(crate) fn undo(&mut self, current: &[EraseRegion]) -> Option<Vec<EraseRegion>> {
+        let previous = self.undo_stack.pop_back()?;
+        self.redo_stack.push(current.to_vec());
+
+        Some(previous)
+    }
+
+    /// Takes the last undone snapshot, remembering `current` so that it can be undone again.
+    pub(crate) fn redo(&mut self, current: &[EraseRegion]) -> Option<Vec<EraseRegion>> {
+        let next = self.redo_stack.pop()?;
+        self.undo_stack.push_back(current.to_vec());
+
+        Some(next)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A snapshot that can be told apart by its only region's x.
+    fn snapshot(x: u8) -> Vec<EraseRegion> {
+        vec![EraseRegion {
+            x,
+            y: 0,
+            width: 1,
+            height: 1,
+        }]
+    }
+
+    fn id(snapshot: &[EraseRegion]) -> u8 {
+        snapshot[0].x
+    }
+
+    #[test]
+    fn edits_can_be_undone_and_redone() {
+        let mut history = RegionHistory::default();
+        history.record(&snapshot(0), &snapshot(1));
+        history.record(&snapshot(1), &snapshot(2));
+
+        assert_eq!(history.undo(&snapshot(2)).as_deref().map(id), Some(1));
+        assert_eq!(history.undo(&snapshot(1)).as_deref().map(id), Some(0));
+        assert!(!history.can_undo());
+        assert!(history.undo(&snapshot(0)).is_none());
+
+        assert_eq!(history.redo(&snapshot(0)).as_deref().map(id), Some(1));
+        assert_eq!(history.redo(&snapshot(1)).as_deref().map(id), Some(2));
+        assert!(!history.can_redo());
+        assert!(history.redo(&snapshot(2)).is_none());
+
+        assert!(history.can_undo());
+    }
+
+    #[test]
+    fn unchanged_regions_are_not_recorded() {
+        let mut history = RegionHistory::default();
+        history.record(&snapshot(3), &snapshot(3));
+
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn new_edits_drop_what_could_be_redone() {
+        let mut history = RegionHistory::default();
+        history.record(&snapshot(0), &snapshot(1));
+        history.undo(&snapshot(1));
+        assert!(history.can_redo());
+
+        history.record(&snapshot(0), &snapshot(2));
+
+        assert!(!history.can_redo());
+        assert_eq!(history.undo(&snapshot(2)).as_deref().map(id), Some(0));
+    }
+
+    #[test]
+    fn only_the_latest_edits_are_kept() {
+        let edits = MAX_HISTORY_SIZE as u8 + 20;
+
+        let mut history = RegionHistory::default();
+        for x in 0..edits {
+            history.record(&snapshot(x), &snapshot(x + 1));
+        }
+
+        let mut current = snapshot(edits);
+        let mut undone = 0;
+        while let Some(previous) = history.undo(&current) {
+            current = previous;
+            undone += 1;
+        }
+
+        assert_eq!(undone, MAX_HISTORY_SIZE);
+        assert_eq!(id(&current), 20);
+
+        // Redoing everything doesn't grow the history past its bound.
+        while let Some(next) = history.redo(&current) {
+            current = next;
+        }
+        assert_eq!(id(&current), edits);
+        assert_eq!(history.undo_stack.len(), MAX_HISTORY_SIZE);
+    }
+
+    #[test]
+    fn clearing_forgets_everything() {
+        let mut history = RegionHistory::default();
+        history.record(&snapshot(0), &snapshot(1));
+        history.record(&snapshot(1), &snapshot(2));
+        history.undo(&snapshot(2));
+
+        history.clear();
+
+        assert!(!history.can_undo());
+        assert!(!history.can_redo());
+    }
+}
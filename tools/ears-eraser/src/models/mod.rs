@@ -1,5 +1,11 @@
+mod body_part;
 mod erase_region;
+mod history;
+mod normalization_report;
 mod workspace;
 
-pub(crate) use erase_region::*;
-pub(crate) use workspace::*;
+pub use body_part::*;
+pub use erase_region::*;
+pub use history::*;
+pub use normalization_report::*;
+pub use workspace::*;
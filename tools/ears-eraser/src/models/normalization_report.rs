@@ -0,0 +1,16 @@
+use crate::models::WasmEraseRegion;
+
+#[derive(Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizationReport {
+    /// Regions that ran past the 64x64 skin area and were cut down to fit it.
+    pub clipped: Vec<WasmEraseRegion>,
+    /// Regions with a width or height of zero, which were dropped.
+    pub removed_empty: Vec<WasmEraseRegion>,
+    /// Regions that were exact copies of an earlier region, which were dropped.
+    pub removed_duplicates: Vec<WasmEraseRegion>,
+    /// Whether overlapping or adjacent regions were merged into a new, smaller set.
+    pub merged: bool,
+    pub regions_before: usize,
+    pub regions_after: usize,
+}
@@ -1,33 +1,160 @@
 use ears_rs::alfalfa::{utils::EraseRegion, AlfalfaData};
-use js_utils::JsResult;
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
-use crate::models::WasmEraseRegion;
+use crate::{
+    body_parts,
+    errors::{from_js_value, to_js_value, Result},
+    logic,
+    models::{RegionHistory, WasmEraseRegion, WasmPartFaceSelector},
+};
 
 #[wasm_bindgen]
 pub struct EarsImageWorkspace {
     pub(crate) alfalfa: AlfalfaData,
     pub(crate) regions: Vec<EraseRegion>,
+    pub(crate) history: RegionHistory,
+    pub(crate) upgraded_from_legacy: bool,
 }
 
 #[wasm_bindgen]
 impl EarsImageWorkspace {
-    pub fn get_regions(&self) -> JsResult<JsValue> {
+    pub fn get_regions(&self) -> Result<JsValue> {
         let wasm_regions: Vec<WasmEraseRegion> =
             self.regions.iter().map(|r| (*r).into()).collect::<Vec<_>>();
 
-        Ok(serde_wasm_bindgen::to_value(&wasm_regions)?)
+        to_js_value(&wasm_regions)
     }
 
-    pub fn set_regions(&mut self, regions: JsValue) -> JsResult<()> {
-        let wasm_regions: Vec<WasmEraseRegion> = serde_wasm_bindgen::from_value(regions)?;
+    /// Whether the skin was a legacy 64x32 skin that got upgraded to 64x64 when decoding.
+    /// Encoding always writes the upgraded skin.
+    pub fn was_upgraded_from_legacy(&self) -> bool {
+        self.upgraded_from_legacy
+    }
+
+    pub fn set_regions(&mut self, regions: JsValue) -> Result<()> {
+        let wasm_regions: Vec<WasmEraseRegion> = from_js_value(regions)?;
         let ears_regions: Vec<EraseRegion> = wasm_regions
             .into_iter()
             .map(|r| r.into())
             .collect::<Vec<_>>();
 
-        self.regions = ears_regions;
+        self.set_erase_regions(ears_regions);
 
         Ok(())
     }
+
+    /// Adds a region for each `{ part, face }` selector, e.g. `{ part: "leftArmLayer", face: "north" }`.
+    pub fn add_part_face_regions(&mut self, selectors: JsValue, slim: bool) -> Result<()> {
+        let selectors: Vec<WasmPartFaceSelector> = from_js_value(selectors)?;
+
+        let mut regions = self.regions.clone();
+        for selector in selectors {
+            regions.push(body_parts::region_for_part_face(
+                selector.part,
+                selector.face,
+                slim,
+            )?);
+        }
+
+        self.set_erase_regions(regions);
+
+        Ok(())
+    }
+
+    /// Lists the body part faces each region overlaps.
+    pub fn label_regions(&self, slim: bool) -> Result<JsValue> {
+        let labels = body_parts::label_regions(&self.regions, slim);
+
+        to_js_value(&labels)
+    }
+
+    /// Restores the regions from before the last edit. Returns whether there was anything to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.history.undo(&self.regions) {
+            Some(regions) => {
+                self.regions = regions;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Re-applies the last undone edit. Returns whether there was anything to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.history.redo(&self.regions) {
+            Some(regions) => {
+                self.regions = regions;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// Renders `skin_bytes` with the current (unsaved) regions erased, scaled up by `scale` (at most
+    /// [`MAX_PREVIEW_SCALE`](crate::preview::MAX_PREVIEW_SCALE)). When `overlay` is set, every
+    /// region is also outlined in its own colour.
+    pub fn render_preview(&self, skin_bytes: &[u8], scale: u32, overlay: bool) -> Result<Vec<u8>> {
+        logic::render_preview(skin_bytes, self, scale, overlay)
+    }
+
+    /// Reports how much of the skin's alfalfa space the data would take with the current regions,
+    /// and whether it fits at all.
+    pub fn get_alfalfa_capacity(&self, skin_bytes: &[u8]) -> Result<JsValue> {
+        let report = logic::alfalfa_capacity(skin_bytes, self)?;
+
+        to_js_value(&report)
+    }
+
+    /// Clips, deduplicates and merges the regions, returning a report of what changed.
+    pub fn normalize_regions(&mut self) -> Result<JsValue> {
+        let report = logic::normalize_workspace_regions(self)?;
+
+        to_js_value(&report)
+    }
+
+    /// Replaces the regions with ones covering every pixel that was made transparent in `erased_bytes`.
+    /// Legacy 64x32 skins are upgraded before comparing.
+    pub fn set_regions_from_erased_skin(
+        &mut self,
+        original_bytes: &[u8],
+        erased_bytes: &[u8],
+    ) -> Result<()> {
+        let regions = logic::regions_from_erased_skin(original_bytes, erased_bytes)?;
+        self.set_erase_regions(regions);
+
+        Ok(())
+    }
+
+    /// Replaces the regions with ones covering every non-transparent pixel of `mask_bytes`. A 64x32
+    /// mask is upgraded like a legacy skin first.
+    pub fn set_regions_from_mask(&mut self, mask_bytes: &[u8]) -> Result<()> {
+        let regions = logic::regions_from_mask(mask_bytes)?;
+        self.set_erase_regions(regions);
+
+        Ok(())
+    }
+}
+
+impl EarsImageWorkspace {
+    pub fn regions(&self) -> &[EraseRegion] {
+        &self.regions
+    }
+
+    /// Replaces the regions, recording the previous ones in the undo history.
+    pub fn set_erase_regions(&mut self, regions: Vec<EraseRegion>) {
+        self.history.record(&self.regions, &regions);
+        self.regions = regions;
+    }
 }
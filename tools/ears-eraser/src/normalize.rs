@@ -0,0 +1,168 @@
+use ears_rs::alfalfa::utils::EraseRegion;
+
+use crate::{
+    errors::*,
+    mask::{EraseMask, MAX_REGION_SIZE, SKIN_SIZE},
+    models::{same_region, NormalizationReport},
+};
+
+/// Clips, deduplicates and merges `regions` into the smallest equivalent set we can find.
+///
+/// Regions that start outside of the skin area cannot be represented and are rejected.
+pub fn normalize_regions(
+    regions: &[EraseRegion],
+) -> Result<(Vec<EraseRegion>, NormalizationReport)> {
+    let mut report = NormalizationReport {
+        regions_before: regions.len(),
+        ..Default::default()
+    };
+
+    let mut cleaned: Vec<EraseRegion> = Vec::with_capacity(regions.len());
+
+    for region in regions {
+        if region.width == 0 || region.height == 0 {
+            report.removed_empty.push((*region).into());
+            continue;
+        }
+
+        if region.x as u32 >= SKIN_SIZE || region.y as u32 >= SKIN_SIZE {
+            return Err(RegionEraserError::RegionOutOfBounds {
+                x: region.x,
+                y: region.y,
+            });
+        }
+
+        let mut region = *region;
+        let max_width = (SKIN_SIZE - region.x as u32) as u8;
+        let max_height = (SKIN_SIZE - region.y as u32) as u8;
+
+        if region.width > max_width || region.height > max_height {
+            report.clipped.push(region.into());
+
+            region.width = region.width.min(max_width);
+            region.height = region.height.min(max_height);
+        }
+
+        if cleaned.iter().any(|r| same_region(r, &region)) {
+            report.removed_duplicates.push(region.into());
+            continue;
+        }
+
+        cleaned.push(region);
+    }
+
+    let merged = EraseMask::from_regions(&cleaned).to_regions();
+    let needs_split = cleaned
+        .iter()
+        .any(|r| r.width > MAX_REGION_SIZE || r.height > MAX_REGION_SIZE);
+
+    let result = if needs_split || merged.len() < cleaned.len() {
+        report.merged = true;
+        merged
+    } else {
+        cleaned
+    };
+
+    report.regions_after = result.len();
+
+    Ok((result, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::WasmEraseRegion;
+
+    use super::*;
+
+    fn region(x: u8, y: u8, width: u8, height: u8) -> EraseRegion {
+        EraseRegion {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn bounds(regions: &[EraseRegion]) -> Vec<(u8, u8, u8, u8)> {
+        regions
+            .iter()
+            .map(|region| (region.x, region.y, region.width, region.height))
+            .collect()
+    }
+
+    fn reported(regions: &[WasmEraseRegion]) -> Vec<(u8, u8, u8, u8)> {
+        let regions: Vec<EraseRegion> = regions.iter().map(|&region| region.into()).collect();
+
+        bounds(&regions)
+    }
+
+    #[test]
+    fn separate_regions_are_kept_as_they_are() {
+        let regions = [region(10, 10, 2, 2), region(0, 0, 4, 4)];
+
+        let (result, report) = normalize_regions(&regions).unwrap();
+
+        assert_eq!(bounds(&result), bounds(&regions));
+        assert!(!report.merged);
+        assert_eq!((report.regions_before, report.regions_after), (2, 2));
+    }
+
+    #[test]
+    fn empty_regions_are_removed() {
+        let (result, report) =
+            normalize_regions(&[region(1, 1, 0, 4), region(2, 2, 2, 2), region(3, 3, 4, 0)])
+                .unwrap();
+
+        assert_eq!(bounds(&result), vec![(2, 2, 2, 2)]);
+        assert_eq!(
+            reported(&report.removed_empty),
+            vec![(1, 1, 0, 4), (3, 3, 4, 0)]
+        );
+    }
+
+    #[test]
+    fn regions_starting_outside_the_skin_are_refused() {
+        assert!(matches!(
+            normalize_regions(&[region(2, 2, 2, 2), region(64, 3, 1, 1)]),
+            Err(RegionEraserError::RegionOutOfBounds { x: 64, y: 3 })
+        ));
+    }
+
+    #[test]
+    fn regions_running_past_the_skin_are_clipped() {
+        let (result, report) = normalize_regions(&[region(60, 62, 10, 10)]).unwrap();
+
+        assert_eq!(bounds(&result), vec![(60, 62, 4, 2)]);
+        assert_eq!(reported(&report.clipped), vec![(60, 62, 10, 10)]);
+    }
+
+    #[test]
+    fn duplicates_are_removed() {
+        let (result, report) =
+            normalize_regions(&[region(5, 5, 3, 3), region(0, 0, 1, 1), region(5, 5, 3, 3)])
+                .unwrap();
+
+        assert_eq!(bounds(&result), vec![(5, 5, 3, 3), (0, 0, 1, 1)]);
+        assert_eq!(reported(&report.removed_duplicates), vec![(5, 5, 3, 3)]);
+        assert!(!report.merged);
+    }
+
+    #[test]
+    fn overlapping_and_adjacent_regions_are_merged() {
+        let (result, report) =
+            normalize_regions(&[region(0, 0, 4, 4), region(4, 0, 4, 4), region(2, 2, 6, 2)])
+                .unwrap();
+
+        assert_eq!(bounds(&result), vec![(0, 0, 8, 4)]);
+        assert!(report.merged);
+        assert_eq!((report.regions_before, report.regions_after), (3, 1));
+    }
+
+    #[test]
+    fn regions_too_large_for_ears_are_split() {
+        let (result, report) = normalize_regions(&[region(0, 0, 40, 8)]).unwrap();
+
+        assert_eq!(bounds(&result), vec![(0, 0, 32, 8), (32, 0, 8, 8)]);
+        assert!(report.merged);
+    }
+}
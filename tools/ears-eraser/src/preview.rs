@@ -0,0 +1,139 @@
+use ears_rs::alfalfa::{
+    utils::{EraseRegion, EraseRegionsProvider},
+    AlfalfaData,
+};
+use image::{imageops::FilterType, Rgba, RgbaImage};
+
+use crate::{errors::*, logic};
+
+/// Largest factor a preview can be scaled up by.
+pub const MAX_PREVIEW_SCALE: u32 = 32;
+
+/// Returns `skin` as the Ears mod would show it with `regions` erased.
+///
+/// The regions are written into a scratch copy of the skin and run through
+/// [`ears_rs::utils::process_erase_regions`], so the preview always matches the mod.
+/// Only the erased pixels are carried over to the result, which keeps the alfalfa payload out of it.
+pub fn apply_erase_regions(
+    skin: &RgbaImage,
+    alfalfa: &AlfalfaData,
+    regions: &[EraseRegion],
+) -> Result<RgbaImage> {
+    let mut alfalfa = alfalfa.clone();
+    alfalfa.set_erase_regions(regions)?;
+
+    let mut encoded = skin.clone();
+    logic::write_alfalfa(&alfalfa, &mut encoded)?;
+
+    let mut processed = encoded.clone();
+    ears_rs::utils::process_erase_regions(&mut processed)?;
+
+    let mut result = skin.clone();
+    for (x, y, pixel) in result.enumerate_pixels_mut() {
+        if processed.get_pixel(x, y) != encoded.get_pixel(x, y) {
+            *pixel = Rgba([0, 0, 0, 0]);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Scales `image` up by `scale`, which must be between 1 and [`MAX_PREVIEW_SCALE`], and outlines
+/// every region in its own colour.
+pub fn draw_region_outlines(
+    image: &RgbaImage,
+    regions: &[EraseRegion],
+    scale: u32,
+) -> Result<RgbaImage> {
+    let invalid_scale = || RegionEraserError::InvalidScale { scale };
+
+    if !(1..=MAX_PREVIEW_SCALE).contains(&scale) {
+        return Err(invalid_scale());
+    }
+
+    let width = image.width().checked_mul(scale).ok_or_else(invalid_scale)?;
+    let height = image
+        .height()
+        .checked_mul(scale)
+        .ok_or_else(invalid_scale)?;
+    let mut result = image::imageops::resize(image, width, height, FilterType::Nearest);
+
+    for (index, region) in regions.iter().enumerate() {
+        let color = region_color(index);
+
+        let min_x = region.x as u32 * scale;
+        let min_y = region.y as u32 * scale;
+        let max_x = ((region.x as u32 + region.width as u32) * scale).min(result.width());
+        let max_y = ((region.y as u32 + region.height as u32) * scale).min(result.height());
+
+        if min_x >= max_x || min_y >= max_y {
+            continue;
+        }
+
+        for x in min_x..max_x {
+            result.put_pixel(x, min_y, color);
+            result.put_pixel(x, max_y - 1, color);
+        }
+
+        for y in min_y..max_y {
+            result.put_pixel(min_x, y, color);
+            result.put_pixel(max_x - 1, y, color);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Picks a fully saturated colour for the region at `index`, spacing hues by the golden angle
+/// so that neighbouring regions never end up with similar colours.
+fn region_color(index: usize) -> Rgba<u8> {
+    let hue = (index as f32 * 137.507_77) % 360.0;
+    let sector = hue / 60.0;
+    let falloff = 1.0 - (sector % 2.0 - 1.0).abs();
+
+    let (r, g, b) = match sector as u32 {
+        0 => (1.0, falloff, 0.0),
+        1 => (falloff, 1.0, 0.0),
+        2 => (0.0, 1.0, falloff),
+        3 => (0.0, falloff, 1.0),
+        4 => (falloff, 0.0, 1.0),
+        _ => (1.0, 0.0, falloff),
+    };
+
+    Rgba([(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales_outside_the_allowed_range_are_refused() {
+        let image = RgbaImage::new(64, 64);
+
+        for scale in [0, MAX_PREVIEW_SCALE + 1, u32::MAX] {
+            assert!(matches!(
+                draw_region_outlines(&image, &[], scale),
+                Err(RegionEraserError::InvalidScale { scale: refused }) if refused == scale
+            ));
+        }
+    }
+
+    #[test]
+    fn regions_are_outlined_at_the_requested_scale() {
+        let image = RgbaImage::from_pixel(64, 64, Rgba([0x40, 0x80, 0xC0, 0xFF]));
+        let region = EraseRegion {
+            x: 8,
+            y: 4,
+            width: 2,
+            height: 3,
+        };
+
+        let preview = draw_region_outlines(&image, &[region], 2).unwrap();
+
+        assert_eq!(preview.dimensions(), (128, 128));
+        assert_eq!(*preview.get_pixel(16, 8), region_color(0));
+        assert_eq!(*preview.get_pixel(19, 13), region_color(0));
+        assert_eq!(*preview.get_pixel(17, 9), Rgba([0x40, 0x80, 0xC0, 0xFF]));
+    }
+}
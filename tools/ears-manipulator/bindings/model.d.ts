@@ -0,0 +1,46 @@
+// Generated from src/model.rs by `UPDATE_BINDINGS=1 cargo test`. Do not edit by hand.
+
+export type WasmEarsFeatures = { ears: WasmEarsSettings, protrusions: Array<WasmProtrusion>, protrusionsSource: WasmTextureSource, tail: WasmTailSettings, snout?: WasmSnoutSettings, wings: WasmWingSettings, cape?: Uint8Array, chestSize: number, alfalfa?: WasmAlfalfaData, emissives: WasmEarsEmissiveData, dataVersion: number, applyTemplate: boolean, };
+
+export type WasmEarsSettings = { mode: WasmEarsMode, anchor: WasmEarsAnchor, source: WasmTextureSource, };
+
+/** 0 = None, 1 = Above, 2 = Sides, 3 = Behind, 4 = Around, 5 = Floppy, 6 = Out, 7 = Cross, 8 = Tall, 9 = TallCross */
+export type WasmEarsMode = 0 | 1 | 2 | 3 | 4 | 5 | 6 | 7 | 8 | 9;
+
+/** 0 = Center, 1 = Front, 2 = Back */
+export type WasmEarsAnchor = 0 | 1 | 2;
+
+/** 0 = Claws, 1 = Horns */
+export type WasmProtrusion = 0 | 1;
+
+export type WasmTailSettings = { mode: WasmTailMode, segments: number, bends: [number, number, number, number], source: WasmTextureSource, };
+
+/** 0 = None, 1 = Down, 2 = Back, 3 = Up, 4 = Vertical, 5 = Cross, 6 = CrossOverlap, 7 = Star, 8 = StarOverlap */
+export type WasmTailMode = 0 | 1 | 2 | 3 | 4 | 5 | 6 | 7 | 8;
+
+export type WasmSnoutSettings = { width: number, height: number, length: number, offset: number, source: WasmTextureSource, };
+
+export type WasmWingSettings = { mode: WasmWingsMode, animations: WasmWingsAnimations, wings?: Uint8Array, source: WasmTextureSource, };
+
+/** 0 = None, 1 = SymmetricDual, 2 = SymmetricSingle, 3 = AsymmetricSingleLeft, 4 = AsymmetricSingleRight, 5 = AsymmetricDual, 6 = Flat */
+export type WasmWingsMode = 0 | 1 | 2 | 3 | 4 | 5 | 6;
+
+/** 0 = Normal, 1 = None */
+export type WasmWingsAnimations = 0 | 1;
+
+/** 0 = SampleSkin, 1 = YourSkin */
+export type WasmTextureSource = 0 | 1;
+
+export type WasmAlfalfaData = { version: number, 
+/**
+ * Serialized as a `Map`, but plain objects are accepted as well.
+ */
+data: Map<string, Uint8Array>, };
+
+export type WasmEarsEmissiveData = { enabled: boolean, palette: Array<number>, };
+
+export type WasmFeaturePreset = { name: string, description: string, 
+/**
+ * Whether the preset ships with this crate rather than being registered from JavaScript.
+ */
+builtin: boolean, features: WasmEarsFeatures, };
@@ -0,0 +1,164 @@
+use ears_rs::{
+    features::EarsFeatures,
+    parser::{v0::writer::EarsWriterV0, v1::writer::EarsWriterV1, EarsFeaturesWriter, EarsParser},
+    utils::errors::Result,
+};
+use image::RgbaImage;
+
+use crate::{
+    diff,
+    model::{WasmEarsFeatures, WasmFeatureChange, WasmVersionConversionReport},
+    vanilla::{clear_area, FEATURE_DATA_AREA},
+};
+
+/// Writes `features` with the writer for their `data_version`.
+pub(crate) fn write_ears_features(image: &mut RgbaImage, features: &EarsFeatures) -> Result<()> {
+    match features.data_version {
+        0 => EarsWriterV0::write(image, features),
+        _ => EarsWriterV1::write(image, features),
+    }
+}
+
+/// Lists every feature of `before` that did not come back the same in `after`.
+fn dropped_features(before: &EarsFeatures, after: Option<&EarsFeatures>) -> Vec<WasmFeatureChange> {
+    let mut dropped = Vec::new();
+
+    // Cape textures live in alfalfa, so only the flag is part of the feature data.
+    diff::compare(
+        &mut dropped,
+        "cape",
+        before.cape_enabled,
+        after.is_some_and(|after| after.cape_enabled),
+    );
+
+    let before = WasmEarsFeatures::from(*before);
+    let after = after.map_or_else(|| diff::without_features(&before), |&after| after.into());
+
+    // Changing the data version is the point of the conversion.
+    dropped.extend(
+        diff::diff_features(&before, &after)
+            .into_iter()
+            .filter(|change| change.field != "dataVersion"),
+    );
+
+    dropped
+}
+
+/// Rewrites the Ears feature data of `skin` in `target_version`, reporting every feature that the
+/// target version couldn't represent. Returns `None` if the skin has no Ears features.
+///
+/// What was dropped is found by parsing the rewritten skin back rather than from a list of known
+/// differences, so lossy values (such as quantized angles) are reported as well.
+///
+/// Only the feature data area is rewritten. The emissive palette and alfalfa data are left as
+/// they are, even when the target version no longer uses them.
+pub(crate) fn convert_data_version(
+    skin: &mut RgbaImage,
+    target_version: u8,
+) -> Result<Option<WasmVersionConversionReport>> {
+    let Some(mut features) = EarsParser::parse(skin)? else {
+        return Ok(None);
+    };
+
+    let from_version = features.data_version;
+    let before = features;
+
+    features.data_version = target_version;
+
+    // Leftovers of the previous encoding could be picked up by the parser otherwise.
+    clear_area(skin, FEATURE_DATA_AREA);
+    write_ears_features(skin, &features)?;
+
+    let after = EarsParser::parse(skin)?;
+
+    Ok(Some(WasmVersionConversionReport {
+        from_version,
+        to_version: target_version,
+        dropped: dropped_features(&before, after.as_ref()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use ears_rs::features::data::{
+        ear::{EarAnchor, EarMode},
+        snout::SnoutData,
+        tail::{TailData, TailMode},
+    };
+    use image::Rgba;
+
+    use super::*;
+
+    fn skin_with(features: &EarsFeatures) -> RgbaImage {
+        let mut skin = RgbaImage::from_pixel(64, 64, Rgba([0x40, 0x80, 0xC0, 0xFF]));
+        write_ears_features(&mut skin, features).unwrap();
+
+        skin
+    }
+
+    fn dropped_fields(report: &WasmVersionConversionReport) -> Vec<&str> {
+        report
+            .dropped
+            .iter()
+            .map(|change| change.field.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn features_missing_from_v0_are_reported() {
+        let mut skin = skin_with(&EarsFeatures {
+            ear_mode: EarMode::Above,
+            tail: Some(TailData {
+                mode: TailMode::Cross,
+                segments: 1,
+                ..Default::default()
+            }),
+            snout: Some(SnoutData {
+                offset: 2,
+                width: 4,
+                height: 2,
+                depth: 2,
+            }),
+            emissive: true,
+            data_version: 1,
+            ..Default::default()
+        });
+
+        let report = convert_data_version(&mut skin, 0).unwrap().unwrap();
+        let dropped = dropped_fields(&report);
+
+        assert_eq!((report.from_version, report.to_version), (1, 0));
+        assert!(dropped.contains(&"snout"), "{dropped:?}");
+        assert!(dropped.contains(&"emissives.enabled"), "{dropped:?}");
+        assert!(dropped.contains(&"tail.mode"), "{dropped:?}");
+        assert!(!dropped.contains(&"ears.mode"), "{dropped:?}");
+
+        // Whatever survived the trip to v0 fits in v1 as well.
+        let report = convert_data_version(&mut skin, 1).unwrap().unwrap();
+
+        assert_eq!((report.from_version, report.to_version), (0, 1));
+        assert_eq!(report.dropped, vec![]);
+    }
+
+    #[test]
+    fn v0_features_survive_a_round_trip() {
+        let mut skin = skin_with(&EarsFeatures {
+            ear_mode: EarMode::Above,
+            ear_anchor: EarAnchor::Front,
+            tail: Some(TailData {
+                mode: TailMode::Down,
+                segments: 1,
+                ..Default::default()
+            }),
+            claws: true,
+            data_version: 1,
+            ..Default::default()
+        });
+
+        let report = convert_data_version(&mut skin, 0).unwrap().unwrap();
+        assert_eq!(report.dropped, vec![]);
+
+        let report = convert_data_version(&mut skin, 1).unwrap().unwrap();
+        assert_eq!(report.dropped, vec![]);
+    }
+}
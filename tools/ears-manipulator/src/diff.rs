@@ -0,0 +1,423 @@
+//! Field-by-field comparison of [`WasmEarsFeatures`], used by [`crate::diff_ears_features`] and
+//! by the report of what [`crate::convert`] loses when changing data versions. The comparison
+//! started out in `convert.rs` and was moved here when the skin diff needed it too.
+
+use std::fmt::Debug;
+
+use serde_bytes::ByteBuf;
+
+use crate::model::{
+    WasmEarsEmissiveData, WasmEarsFeatures, WasmEarsFeaturesDiff, WasmEarsMode, WasmEarsSettings,
+    WasmFeatureChange, WasmSnoutSettings, WasmTailMode, WasmTailSettings, WasmWingSettings,
+    WasmWingsAnimations, WasmWingsMode,
+};
+
+fn push_change(changes: &mut Vec<WasmFeatureChange>, field: &str, before: String, after: String) {
+    changes.push(WasmFeatureChange {
+        field: field.to_owned(),
+        before,
+        after,
+    });
+}
+
+pub(crate) fn compare<T: PartialEq + Debug>(
+    changes: &mut Vec<WasmFeatureChange>,
+    field: &str,
+    before: T,
+    after: T,
+) {
+    if before != after {
+        push_change(changes, field, format!("{before:?}"), format!("{after:?}"));
+    }
+}
+
+fn describe_texture(texture: Option<&ByteBuf>) -> String {
+    texture.map_or_else(|| "none".to_owned(), |t| format!("{} bytes", t.len()))
+}
+
+/// A short FNV-1a fingerprint of a texture, to tell apart textures of the same size.
+fn texture_fingerprint(texture: &[u8]) -> u32 {
+    texture.iter().fold(0x811C9DC5, |hash, &byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x01000193)
+    })
+}
+
+fn compare_texture(
+    changes: &mut Vec<WasmFeatureChange>,
+    field: &str,
+    before: Option<&ByteBuf>,
+    after: Option<&ByteBuf>,
+) {
+    if before == after {
+        return;
+    }
+
+    // Sizes alone would make a changed texture look the same, so fingerprints are added.
+    let same_size = before.map(|t| t.len()) == after.map(|t| t.len());
+    let describe = |texture: Option<&ByteBuf>| match texture {
+        Some(t) if same_size => format!("{} bytes, hash {:08x}", t.len(), texture_fingerprint(t)),
+        _ => describe_texture(texture),
+    };
+
+    push_change(changes, field, describe(before), describe(after));
+}
+
+fn describe_snout(snout: Option<&WasmSnoutSettings>) -> String {
+    snout.map_or_else(
+        || "none".to_owned(),
+        |s| {
+            format!(
+                "{}x{}x{} at offset {}",
+                s.width, s.height, s.length, s.offset
+            )
+        },
+    )
+}
+
+fn describe_color(color: Option<&u32>) -> String {
+    color.map_or_else(|| "none".to_owned(), |c| format!("#{:06X}", c & 0xFFFFFF))
+}
+
+/// What `features` look like with every Ears feature turned off, for comparing against a skin that
+/// has none. Everything that isn't a feature, such as texture sources, is kept.
+pub(crate) fn without_features(features: &WasmEarsFeatures) -> WasmEarsFeatures {
+    WasmEarsFeatures {
+        ears: WasmEarsSettings {
+            mode: WasmEarsMode::None,
+            ..features.ears
+        },
+        protrusions: Vec::new(),
+        tail: WasmTailSettings {
+            mode: WasmTailMode::None,
+            segments: 0,
+            bends: [0.0; 4],
+            ..features.tail
+        },
+        snout: None,
+        wings: WasmWingSettings {
+            mode: WasmWingsMode::None,
+            animations: WasmWingsAnimations::None,
+            wings: None,
+            ..features.wings
+        },
+        cape: None,
+        chest_size: 0.0,
+        alfalfa: None,
+        emissives: WasmEarsEmissiveData {
+            enabled: false,
+            palette: Vec::new(),
+        },
+        ..features.clone()
+    }
+}
+
+/// Lists every field that differs between `before` and `after`, using the same JSON paths as the
+/// serialized features, such as `tail.bends[2]`. Texture sources and raw alfalfa are left out.
+pub(crate) fn diff_features(
+    before: &WasmEarsFeatures,
+    after: &WasmEarsFeatures,
+) -> Vec<WasmFeatureChange> {
+    let mut changes = Vec::new();
+
+    compare(
+        &mut changes,
+        "dataVersion",
+        before.data_version,
+        after.data_version,
+    );
+
+    compare(&mut changes, "ears.mode", before.ears.mode, after.ears.mode);
+    compare(
+        &mut changes,
+        "ears.anchor",
+        before.ears.anchor,
+        after.ears.anchor,
+    );
+    compare(
+        &mut changes,
+        "protrusions",
+        &before.protrusions,
+        &after.protrusions,
+    );
+
+    compare(&mut changes, "tail.mode", before.tail.mode, after.tail.mode);
+    compare(
+        &mut changes,
+        "tail.segments",
+        before.tail.segments,
+        after.tail.segments,
+    );
+    // Bends past the last segment aren't used, so they don't count as a change.
+    let bends = usize::from(before.tail.segments.max(after.tail.segments)).min(4);
+    for index in 0..bends {
+        compare(
+            &mut changes,
+            &format!("tail.bends[{index}]"),
+            before.tail.bends[index],
+            after.tail.bends[index],
+        );
+    }
+
+    match (&before.snout, &after.snout) {
+        (Some(before), Some(after)) => {
+            compare(&mut changes, "snout.width", before.width, after.width);
+            compare(&mut changes, "snout.height", before.height, after.height);
+            compare(&mut changes, "snout.length", before.length, after.length);
+            compare(&mut changes, "snout.offset", before.offset, after.offset);
+        }
+        (None, None) => {}
+        (before, after) => push_change(
+            &mut changes,
+            "snout",
+            describe_snout(before.as_ref()),
+            describe_snout(after.as_ref()),
+        ),
+    }
+
+    compare(
+        &mut changes,
+        "wings.mode",
+        before.wings.mode,
+        after.wings.mode,
+    );
+    compare(
+        &mut changes,
+        "wings.animations",
+        before.wings.animations,
+        after.wings.animations,
+    );
+    compare_texture(
+        &mut changes,
+        "wings.wings",
+        before.wings.wings.as_ref(),
+        after.wings.wings.as_ref(),
+    );
+
+    compare_texture(
+        &mut changes,
+        "cape",
+        before.cape.as_ref(),
+        after.cape.as_ref(),
+    );
+
+    compare(
+        &mut changes,
+        "chestSize",
+        before.chest_size,
+        after.chest_size,
+    );
+
+    compare(
+        &mut changes,
+        "emissives.enabled",
+        before.emissives.enabled,
+        after.emissives.enabled,
+    );
+    let colors = before
+        .emissives
+        .palette
+        .len()
+        .max(after.emissives.palette.len());
+    for index in 0..colors {
+        let before = before.emissives.palette.get(index);
+        let after = after.emissives.palette.get(index);
+
+        if before != after {
+            push_change(
+                &mut changes,
+                &format!("emissives.palette[{index}]"),
+                describe_color(before),
+                describe_color(after),
+            );
+        }
+    }
+
+    changes
+}
+
+/// Diffs the features of two skins, either of which may not have any Ears features at all.
+pub(crate) fn diff_skins(
+    before: Option<&WasmEarsFeatures>,
+    after: Option<&WasmEarsFeatures>,
+) -> WasmEarsFeaturesDiff {
+    let changes = match (before, after) {
+        (Some(before), Some(after)) => diff_features(before, after),
+        (Some(before), None) => diff_features(before, &without_features(before)),
+        (None, Some(after)) => diff_features(&without_features(after), after),
+        (None, None) => Vec::new(),
+    };
+
+    WasmEarsFeaturesDiff {
+        before_has_features: before.is_some(),
+        after_has_features: after.is_some(),
+        changes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ears_rs::features::{
+        data::{
+            ear::EarMode,
+            snout::SnoutData,
+            tail::{TailData, TailMode},
+        },
+        EarsFeatures,
+    };
+
+    use super::*;
+
+    fn sample_features() -> WasmEarsFeatures {
+        let mut features = WasmEarsFeatures::from(EarsFeatures {
+            ear_mode: EarMode::Above,
+            tail: Some(TailData {
+                mode: TailMode::Down,
+                segments: 2,
+                bends: [10.0, 20.0, 0.0, 0.0],
+            }),
+            snout: Some(SnoutData {
+                offset: 2,
+                width: 4,
+                height: 2,
+                depth: 2,
+            }),
+            emissive: true,
+            data_version: 1,
+            ..Default::default()
+        });
+        features.emissives.palette = vec![0xFF0000, 0x00FF00];
+
+        features
+    }
+
+    fn fields(changes: &[WasmFeatureChange]) -> Vec<&str> {
+        changes.iter().map(|change| change.field.as_str()).collect()
+    }
+
+    #[test]
+    fn skins_without_features_have_no_changes() {
+        let diff = diff_skins(None, None);
+
+        assert!(!diff.before_has_features);
+        assert!(!diff.after_has_features);
+        assert_eq!(diff.changes, vec![]);
+    }
+
+    #[test]
+    fn identical_features_have_no_changes() {
+        let features = sample_features();
+
+        let diff = diff_skins(Some(&features), Some(&features.clone()));
+
+        assert!(diff.before_has_features);
+        assert!(diff.after_has_features);
+        assert_eq!(diff.changes, vec![]);
+    }
+
+    #[test]
+    fn removed_features_are_compared_against_none() {
+        let features = sample_features();
+
+        let removed = diff_skins(Some(&features), None);
+        let added = diff_skins(None, Some(&features));
+
+        assert!(removed.before_has_features);
+        assert!(!removed.after_has_features);
+        assert_eq!(
+            fields(&removed.changes),
+            vec![
+                "ears.mode",
+                "tail.mode",
+                "tail.segments",
+                "tail.bends[0]",
+                "tail.bends[1]",
+                "snout",
+                "emissives.enabled",
+                "emissives.palette[0]",
+                "emissives.palette[1]",
+            ]
+        );
+
+        assert!(!added.before_has_features);
+        assert!(added.after_has_features);
+        assert_eq!(added.changes.len(), removed.changes.len());
+        for (added, removed) in added.changes.iter().zip(&removed.changes) {
+            assert_eq!(added.field, removed.field);
+            assert_eq!(
+                (&added.before, &added.after),
+                (&removed.after, &removed.before)
+            );
+        }
+    }
+
+    #[test]
+    fn bends_past_the_last_segment_are_ignored() {
+        let before = sample_features();
+        let mut after = before.clone();
+        after.tail.bends[1] = 25.0;
+        after.tail.bends[3] = 45.0;
+
+        assert_eq!(
+            fields(&diff_features(&before, &after)),
+            vec!["tail.bends[1]"]
+        );
+    }
+
+    #[test]
+    fn palettes_of_different_lengths_are_compared_color_by_color() {
+        let before = sample_features();
+
+        let mut longer = before.clone();
+        longer.emissives.palette.push(0x0000FF);
+        assert_eq!(
+            diff_features(&before, &longer),
+            vec![WasmFeatureChange {
+                field: "emissives.palette[2]".to_owned(),
+                before: "none".to_owned(),
+                after: "#0000FF".to_owned(),
+            }]
+        );
+
+        let mut shorter = before.clone();
+        shorter.emissives.palette.pop();
+        assert_eq!(
+            diff_features(&before, &shorter),
+            vec![WasmFeatureChange {
+                field: "emissives.palette[1]".to_owned(),
+                before: "#00FF00".to_owned(),
+                after: "none".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn textures_are_described_by_size() {
+        let before = sample_features();
+        let mut after = before.clone();
+        after.cape = Some(ByteBuf::from(vec![1, 2, 3]));
+
+        assert_eq!(
+            diff_features(&before, &after),
+            vec![WasmFeatureChange {
+                field: "cape".to_owned(),
+                before: "none".to_owned(),
+                after: "3 bytes".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn textures_of_the_same_size_are_told_apart() {
+        let mut before = sample_features();
+        before.cape = Some(ByteBuf::from(vec![1, 2, 3]));
+        let mut after = before.clone();
+        after.cape = Some(ByteBuf::from(vec![3, 2, 1]));
+
+        let changes = diff_features(&before, &after);
+
+        assert_eq!(fields(&changes), vec!["cape"]);
+        assert!(changes[0].before.starts_with("3 bytes, hash "));
+        assert!(changes[0].after.starts_with("3 bytes, hash "));
+        assert_ne!(changes[0].before, changes[0].after);
+    }
+}
@@ -1,37 +1,48 @@
-use std::{borrow::Borrow, io::Cursor};
+use std::{borrow::Borrow, cell::RefCell, io::Cursor};
 
 use ears_rs::{
     alfalfa::{self, AlfalfaData},
     features::EarsFeatures,
-    parser::{v0::writer::EarsWriterV0, v1::writer::EarsWriterV1, EarsFeaturesWriter, EarsParser}, utils::{self, EarsEmissivePalette},
+    parser::EarsParser,
+    utils::{self, EarsEmissivePalette},
 };
 use image::{ImageFormat, RgbaImage};
-use js_sys::Uint8Array;
+use js_sys::{Reflect, Uint8Array};
 use js_utils::JsResult;
+use skin_utils::{
+    optimize::{optimize_alfalfa_images, SkinWriteOptions},
+    png::{encode_png, PngEncodeOptions},
+};
 use wasm_bindgen::prelude::*;
 
-use crate::model::WasmEarsFeatures;
+use crate::model::{
+    WasmApplyFeaturesResult, WasmApplyPresetOptions, WasmEarsFeatures, WasmFeatureIssue,
+    WasmVanillaResult, WasmVersionConversionResult,
+};
 
+mod convert;
+mod diff;
 mod model;
+mod presets;
+mod typescript;
+mod validation;
+mod vanilla;
 
 #[cfg(feature = "template")]
 mod template;
 
-#[wasm_bindgen]
+thread_local! {
+    /// Presets registered from JavaScript through [`register_presets`].
+    static PRESETS: RefCell<presets::PresetCatalogue> = RefCell::default();
+}
+
+#[wasm_bindgen(unchecked_return_type = "WasmEarsFeatures | null")]
 pub fn get_ears_features(skin_data: &[u8]) -> JsResult<JsValue> {
     console_error_panic_hook::set_once();
 
     let skin_image = image::load_from_memory(skin_data)?.into_rgba8();
 
-    let features = EarsParser::parse(&skin_image)?;
-    let alfalfa = alfalfa::read_alfalfa(&skin_image)?;
-    
-    let emissive_palette = utils::extract_emissive_palette(&skin_image)?;
-    
-    let value = features
-        .map(|f| Into::<WasmEarsFeatures>::into(f))
-        .map(|f| f.with_alfalfa(alfalfa))
-        .map(|f| f.with_emissive(emissive_palette))
+    let value = read_ears_features(&skin_image)?
         .map(|f| serde_wasm_bindgen::to_value(&f))
         .transpose()?
         .unwrap_or(JsValue::NULL);
@@ -39,8 +50,23 @@ pub fn get_ears_features(skin_data: &[u8]) -> JsResult<JsValue> {
     return Ok(value);
 }
 
+/// Parses the Ears features of a skin along with its alfalfa data and emissive palette.
+fn read_ears_features(skin_image: &RgbaImage) -> JsResult<Option<WasmEarsFeatures>> {
+    let features = EarsParser::parse(skin_image)?;
+    let alfalfa = alfalfa::read_alfalfa(skin_image)?;
+    
+    let emissive_palette = utils::extract_emissive_palette(skin_image)?;
+    
+    Ok(features
+        .map(|f| Into::<WasmEarsFeatures>::into(f))
+        .map(|f| f.with_alfalfa(alfalfa))
+        .map(|f| f.with_emissive(emissive_palette)))
+}
+
 #[wasm_bindgen]
-pub fn get_template_skin(features: JsValue) -> JsResult<Uint8Array> {
+pub fn get_template_skin(
+    #[wasm_bindgen(unchecked_param_type = "WasmEarsFeatures")] features: JsValue,
+) -> JsResult<Uint8Array> {
     console_error_panic_hook::set_once();
 
     let wasm_features: WasmEarsFeatures = serde_wasm_bindgen::from_value(features)?;
@@ -61,10 +87,81 @@ pub fn get_template_skin(features: JsValue) -> JsResult<Uint8Array> {
 }
 
 #[wasm_bindgen]
-pub fn apply_features(skin_data: &[u8], features: JsValue) -> JsResult<Uint8Array> {
+pub fn apply_features(
+    skin_data: &[u8],
+    #[wasm_bindgen(unchecked_param_type = "WasmEarsFeatures")] features: JsValue,
+) -> JsResult<Uint8Array> {
+    apply_features_with_options(skin_data, features, JsValue::UNDEFINED)
+}
+
+/// Like [`apply_features`], but with control over the PNG compression and filter,
+/// over whether the metadata of the original file is kept, and over whether the cape and
+/// wings are re-encoded as small as possible first.
+#[wasm_bindgen]
+pub fn apply_features_with_options(
+    skin_data: &[u8],
+    #[wasm_bindgen(unchecked_param_type = "WasmEarsFeatures")] features: JsValue,
+    options: JsValue,
+) -> JsResult<Uint8Array> {
+    let result = apply_features_with_report_impl(skin_data, features, options)?;
+
+    Ok(Uint8Array::from(result.skin.as_slice()))
+}
+
+/// Like [`apply_features_with_options`], but also reports how many bytes re-encoding the cape
+/// and wings saved.
+#[wasm_bindgen]
+pub fn apply_features_with_report(
+    skin_data: &[u8],
+    #[wasm_bindgen(unchecked_param_type = "WasmEarsFeatures")] features: JsValue,
+    options: JsValue,
+) -> JsResult<JsValue> {
+    let result = apply_features_with_report_impl(skin_data, features, options)?;
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+fn apply_features_with_report_impl(
+    skin_data: &[u8],
+    features: JsValue,
+    options: JsValue,
+) -> JsResult<WasmApplyFeaturesResult> {
     console_error_panic_hook::set_once();
 
+    let options: SkinWriteOptions =
+        serde_wasm_bindgen::from_value::<Option<_>>(options)?.unwrap_or_default();
+
     let wasm_features: WasmEarsFeatures = serde_wasm_bindgen::from_value(features)?;
+
+    write_features(skin_data, wasm_features, &options)
+}
+
+/// The error thrown for features Ears can't encode. Besides the message, it has an `issues`
+/// property with the same problems [`validate_features`] returns, so that editors can point at
+/// the offending fields.
+fn invalid_features_error(issues: &[WasmFeatureIssue]) -> JsError {
+    let messages: Vec<_> = issues.iter().map(|issue| issue.message.as_str()).collect();
+    let error = JsError::new(&format!("Invalid features: {}", messages.join("; ")));
+
+    // The clone refers to the same JavaScript object, so the property ends up on `error`.
+    let js_error = JsValue::from(error.clone());
+    if let Ok(issues) = serde_wasm_bindgen::to_value(issues) {
+        let _ = Reflect::set(&js_error, &"issues".into(), &issues);
+    }
+
+    error
+}
+
+/// Writes `wasm_features` into a skin, refusing features Ears can't encode.
+fn write_features(
+    skin_data: &[u8],
+    wasm_features: WasmEarsFeatures,
+    options: &SkinWriteOptions,
+) -> JsResult<WasmApplyFeaturesResult> {
+    let issues = validation::validate_features(&wasm_features);
+    if !issues.is_empty() {
+        return Err(invalid_features_error(&issues));
+    }
     
     let mut skin_image = image::load_from_memory(skin_data)?.into_rgba8();
     
@@ -77,14 +174,15 @@ pub fn apply_features(skin_data: &[u8], features: JsValue) -> JsResult<Uint8Arra
 
     let features: EarsFeatures = wasm_features.clone().into();
     let emissive_palette: EarsEmissivePalette = wasm_features.borrow().into();
-    let alfalfa_data: AlfalfaData = wasm_features.into();
+    let mut alfalfa_data: AlfalfaData = wasm_features.into();
     
-    let writer = match features.data_version {
-        0 => |image: &mut RgbaImage, features: &EarsFeatures| EarsWriterV0::write(image, features),
-        _ => |image: &mut RgbaImage, features: &EarsFeatures| EarsWriterV1::write(image, features),
+    convert::write_ears_features(&mut skin_image, &features)?;
+
+    let optimization = if options.optimize_images {
+        optimize_alfalfa_images(&mut alfalfa_data)
+    } else {
+        Default::default()
     };
-    
-    (writer)(&mut skin_image, &features)?;
 
     if !alfalfa_data.is_empty() {
         alfalfa::write_alfalfa(&alfalfa_data, &mut skin_image)?;
@@ -93,11 +191,152 @@ pub fn apply_features(skin_data: &[u8], features: JsValue) -> JsResult<Uint8Arra
     if !emissive_palette.0.is_empty() {
         utils::write_emissive_palette(&mut skin_image, &emissive_palette)?;
     }
-    
-    let mut bytes = Vec::new();
-    {
-        skin_image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+
+    let bytes = encode_png(&skin_image, Some(skin_data), &options.png)?;
+
+    Ok(WasmApplyFeaturesResult {
+        skin: bytes.into(),
+        optimization,
+    })
+}
+
+/// Checks features against what Ears can encode, returning every problem with the JSON path of
+/// the offending field and its allowed range. [`apply_features`] refuses features with problems,
+/// throwing an `Error` whose `issues` property holds the same list.
+#[wasm_bindgen]
+pub fn validate_features(
+    #[wasm_bindgen(unchecked_param_type = "WasmEarsFeatures")] features: JsValue,
+) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let wasm_features: WasmEarsFeatures = serde_wasm_bindgen::from_value(features)?;
+
+    let issues = validation::validate_features(&wasm_features);
+
+    Ok(serde_wasm_bindgen::to_value(&issues)?)
+}
+
+/// Strips every trace of Ears from a skin, returning the vanilla skin and a report of what was removed.
+#[wasm_bindgen]
+pub fn make_vanilla(skin_data: &[u8], options: JsValue) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let options: Option<PngEncodeOptions> = serde_wasm_bindgen::from_value(options)?;
+
+    let mut skin_image = image::load_from_memory(skin_data)?.into_rgba8();
+
+    let report = vanilla::make_vanilla(&mut skin_image);
+    let bytes = encode_png(&skin_image, Some(skin_data), &options.unwrap_or_default())?;
+
+    let result = WasmVanillaResult {
+        skin: bytes.into(),
+        report,
+    };
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+/// Rewrites the Ears feature data of a skin in another data version (0 or 1), reporting every
+/// feature the target version couldn't represent. Only the feature data is rewritten: alfalfa
+/// data and the emissive palette stay on the skin, even when converting to version 0 drops the
+/// features that use them.
+#[wasm_bindgen]
+pub fn convert_data_version(
+    skin_data: &[u8],
+    target_version: u8,
+    options: JsValue,
+) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let (min, max) = validation::DATA_VERSIONS;
+    if !(min..=max).contains(&target_version) {
+        return Err(JsError::new(&format!(
+            "Unsupported data version {target_version}, expected {min} to {max}"
+        )));
     }
 
-    Ok(Uint8Array::from(bytes.as_slice()))
+    let options: Option<PngEncodeOptions> = serde_wasm_bindgen::from_value(options)?;
+
+    let mut skin_image = image::load_from_memory(skin_data)?.into_rgba8();
+
+    let report = convert::convert_data_version(&mut skin_image, target_version)?
+        .ok_or_else(|| JsError::new("Skin has no Ears features to convert"))?;
+    let bytes = encode_png(&skin_image, Some(skin_data), &options.unwrap_or_default())?;
+
+    let result = WasmVersionConversionResult {
+        skin: bytes.into(),
+        report,
+    };
+
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+/// Compares the Ears features of two skins, listing every field that changed with its JSON path
+/// and its value before and after. Either skin may have no Ears features at all.
+#[wasm_bindgen]
+pub fn diff_ears_features(before_skin: &[u8], after_skin: &[u8]) -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let before_image = image::load_from_memory(before_skin)?.into_rgba8();
+    let after_image = image::load_from_memory(after_skin)?.into_rgba8();
+
+    let before = read_ears_features(&before_image)?;
+    let after = read_ears_features(&after_image)?;
+
+    let diff = diff::diff_skins(before.as_ref(), after.as_ref());
+
+    Ok(serde_wasm_bindgen::to_value(&diff)?)
+}
+
+/// Lists the built-in presets along with any registered through [`register_presets`].
+#[wasm_bindgen(unchecked_return_type = "WasmFeaturePreset[]")]
+pub fn list_presets() -> JsResult<JsValue> {
+    console_error_panic_hook::set_once();
+
+    let presets = PRESETS.with_borrow(|catalogue| catalogue.list());
+
+    Ok(serde_wasm_bindgen::to_value(&presets)?)
+}
+
+/// Registers custom presets from a JSON array of `{ name, description, features }` objects,
+/// returning how many names were registered. Names are compared ignoring case, and a custom
+/// preset hides a built-in one of the same name.
+#[wasm_bindgen]
+pub fn register_presets(json: &str) -> JsResult<usize> {
+    console_error_panic_hook::set_once();
+
+    PRESETS
+        .with_borrow_mut(|catalogue| catalogue.register_json(json))
+        .map_err(|message| JsError::new(&message))
+}
+
+/// Forgets every preset registered through [`register_presets`], bringing back the built-in
+/// presets they were hiding.
+#[wasm_bindgen]
+pub fn clear_custom_presets() {
+    PRESETS.with_borrow_mut(|catalogue| catalogue.clear_custom());
+}
+
+/// Gives a skin the features of the named preset, keeping its textures, alfalfa data and emissive
+/// palette. With `applyTemplate` set, the template texture is painted for the parts of the preset.
+#[wasm_bindgen]
+pub fn apply_preset(skin_data: &[u8], name: &str, options: JsValue) -> JsResult<Uint8Array> {
+    console_error_panic_hook::set_once();
+
+    let options: WasmApplyPresetOptions =
+        serde_wasm_bindgen::from_value::<Option<_>>(options)?.unwrap_or_default();
+
+    let preset = PRESETS
+        .with_borrow(|catalogue| catalogue.find(name))
+        .ok_or_else(|| JsError::new(&format!("Unknown preset {name}")))?;
+
+    let skin_image = image::load_from_memory(skin_data)?.into_rgba8();
+    let existing = read_ears_features(&skin_image)?;
+
+    let mut features = presets::apply_preset_features(&preset.features, existing)?;
+    features.apply_template = options.apply_template;
+
+    let result = write_features(skin_data, features, &options.write)?;
+
+    Ok(Uint8Array::from(result.skin.as_slice()))
 }
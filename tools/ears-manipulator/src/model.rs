@@ -1,101 +1,5 @@
-/* export enum TextureSource {
-    SampleSkin,
-    YourSkin,
-}
-
-export interface WasmEarsFeatures {
-    ears: EarsSettings;
-    protrusions: Set<Protrusion>;
-    tail: TailSettings;
-    snout?: SnoutSettings;
-    wings: WingSettings;
-    cape?: Uint8Array;
-    chestSize: number;
-    alfalfa?: WasmAlfalfaData;
-}
-
-export interface WasmAlfalfaData {
-    version: number;
-    data: Record<string, Uint8Array>;
-}
-
-export interface WasmEarsSettings {
-    mode: EarsMode;
-    anchor: EarsAnchor;
-    source: TextureSource;
-}
-
-export interface WasmTailSettings {
-    mode: TailMode;
-    segments: 1 | 2 | 3 | 4;
-    bends: number[];
-    source: TextureSource;
-}
-
-export enum WasmSnoutStatus {
-    Disabled,
-    Enabled,
-}
-
-export interface WasmSnoutSettings {
-    width: number;
-    height: number;
-    length: number;
-    offset: number;
-    source: TextureSource;
-}
-
-export interface WasmWingSettings {
-    mode: WingsMode;
-    animations: WingsAnimations;
-    wings: Uint8Array;
-    source: TextureSource;
-}
-
-export enum WasmEarsMode {
-    None,
-    Above,
-    Sides,
-    Behind,
-    Around,
-    Floppy,
-    Out,
-    Cross,
-    Tall,
-    TallCross
-}
-
-export enum WasmEarsAnchor {
-    Center,
-    Front,
-    Back
-}
-
-export enum WasmProtrusion {
-    Claws,
-    Horns
-}
-
-export enum WasmTailMode {
-    None,
-    Down,
-    Back,
-    Up,
-    Vertical
-}
-
-export enum WasmWingsMode {
-    None,
-    SymmetricDual,
-    SymmetricSingle,
-    AsymmetricSingleLeft,
-    AsymmetricSingleRight,
-}
-
-export enum WasmWingsAnimations {
-    Normal,
-    None
-} */
+// The `TS` derive of `repr(enum)` enums parses their discriminants with `from_str_radix`.
+#![cfg_attr(test, allow(clippy::from_str_radix_10))]
 
 use std::collections::HashMap;
 
@@ -115,10 +19,15 @@ use ears_rs::{
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use skin_utils::optimize::{ImageOptimizationReport, SkinWriteOptions};
 use strum::EnumIs;
+#[cfg(test)]
+use ts_rs::TS;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize_repr, Deserialize_repr)]
+#[cfg_attr(test, derive(TS))]
 #[repr(u8)]
+#[cfg_attr(test, ts(repr(enum)))]
 pub(crate) enum WasmEarsMode {
     #[default]
     None,
@@ -168,7 +77,9 @@ impl From<EarMode> for WasmEarsMode {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize_repr, Serialize_repr)]
+#[cfg_attr(test, derive(TS))]
 #[repr(u8)]
+#[cfg_attr(test, ts(repr(enum)))]
 pub(crate) enum WasmEarsAnchor {
     Center,
     Front,
@@ -196,14 +107,18 @@ impl From<EarAnchor> for WasmEarsAnchor {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize_repr, Serialize_repr)]
+#[cfg_attr(test, derive(TS))]
 #[repr(u8)]
+#[cfg_attr(test, ts(repr(enum)))]
 pub(crate) enum WasmProtrusion {
     Claws,
     Horns,
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize_repr, Serialize_repr)]
+#[cfg_attr(test, derive(TS))]
 #[repr(u8)]
+#[cfg_attr(test, ts(repr(enum)))]
 pub(crate) enum WasmTailMode {
     #[default]
     None,
@@ -250,7 +165,9 @@ impl From<TailMode> for WasmTailMode {
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize_repr, Serialize_repr)]
+#[cfg_attr(test, derive(TS))]
 #[repr(u8)]
+#[cfg_attr(test, ts(repr(enum)))]
 pub(crate) enum WasmWingsMode {
     #[default]
     None,
@@ -291,14 +208,18 @@ impl From<WingMode> for WasmWingsMode {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIs, Deserialize_repr, Serialize_repr)]
+#[cfg_attr(test, derive(TS))]
 #[repr(u8)]
+#[cfg_attr(test, ts(repr(enum)))]
 pub(crate) enum WasmTextureSource {
     SampleSkin,
     YourSkin,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize_repr, Serialize_repr)]
+#[cfg_attr(test, derive(TS))]
 #[repr(u8)]
+#[cfg_attr(test, ts(repr(enum)))]
 pub(crate) enum WasmWingsAnimations {
     Normal,
     None,
@@ -313,6 +234,7 @@ pub(crate) enum WasmSnoutStatus {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(TS))]
 pub(crate) struct WasmSnoutSettings {
     pub(crate) width: u8,
     pub(crate) height: u8,
@@ -322,9 +244,11 @@ pub(crate) struct WasmSnoutSettings {
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(TS))]
 pub(crate) struct WasmWingSettings {
     pub(crate) mode: WasmWingsMode,
     pub(crate) animations: WasmWingsAnimations,
+    #[cfg_attr(test, ts(optional, type = "Uint8Array"))]
     pub(crate) wings: Option<ByteBuf>,
     pub(crate) source: WasmTextureSource,
 }
@@ -357,6 +281,7 @@ impl From<WingData> for WasmWingSettings {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(TS))]
 pub(crate) struct WasmTailSettings {
     pub(crate) mode: WasmTailMode,
     pub(crate) segments: u8,
@@ -409,6 +334,7 @@ impl From<SnoutData> for WasmSnoutSettings {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(TS))]
 pub(crate) struct WasmEarsSettings {
     pub(crate) mode: WasmEarsMode,
     pub(crate) anchor: WasmEarsAnchor,
@@ -416,8 +342,11 @@ pub(crate) struct WasmEarsSettings {
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(TS))]
 pub(crate) struct WasmAlfalfaData {
     pub(crate) version: u8,
+    /// Serialized as a `Map`, but plain objects are accepted as well.
+    #[cfg_attr(test, ts(type = "Map<string, Uint8Array>"))]
     pub(crate) data: HashMap<String, ByteBuf>,
 }
 
@@ -448,29 +377,130 @@ impl From<AlfalfaData> for WasmAlfalfaData {
     }
 }
 
+/// What [`crate::make_vanilla`] removed from a skin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WasmVanillaReport {
+    /// The data version of the Ears features that were removed, if the skin had any.
+    pub(crate) feature_data_version: Option<u8>,
+    pub(crate) alfalfa_keys: Vec<String>,
+    pub(crate) emissive_colors: usize,
+    /// The Ears data that couldn't be read, such as corrupt alfalfa. It is removed all the same.
+    pub(crate) read_errors: Vec<String>,
+    /// Feature data and palette pixels that were made transparent.
+    pub(crate) cleared_pixels: usize,
+    /// Pixels whose alpha was changed to make the base layer opaque.
+    pub(crate) stripped_alpha_pixels: usize,
+    /// Whether the result no longer has anything Ears would pick up.
+    pub(crate) clean: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct WasmVanillaResult {
+    pub(crate) skin: ByteBuf,
+    pub(crate) report: WasmVanillaReport,
+}
+
+/// A field of [`WasmEarsFeatures`] that Ears can't encode as given.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct WasmFeatureIssue {
+    pub(crate) field: String,
+    pub(crate) message: String,
+    pub(crate) min: Option<f64>,
+    pub(crate) max: Option<f64>,
+}
+
+/// A field of [`WasmEarsFeatures`] that differs between two sets of features.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct WasmFeatureChange {
+    /// JSON path of the field in [`WasmEarsFeatures`], such as `tail.mode`.
+    pub(crate) field: String,
+    pub(crate) before: String,
+    pub(crate) after: String,
+}
+
+/// What [`crate::convert_data_version`] changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WasmVersionConversionReport {
+    pub(crate) from_version: u8,
+    pub(crate) to_version: u8,
+    /// Features that didn't come back the same in the target version.
+    pub(crate) dropped: Vec<WasmFeatureChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct WasmVersionConversionResult {
+    pub(crate) skin: ByteBuf,
+    pub(crate) report: WasmVersionConversionReport,
+}
+
+/// The feature-level differences between two skins, as returned by [`crate::diff_ears_features`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WasmEarsFeaturesDiff {
+    pub(crate) before_has_features: bool,
+    pub(crate) after_has_features: bool,
+    pub(crate) changes: Vec<WasmFeatureChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct WasmApplyFeaturesResult {
+    pub(crate) skin: ByteBuf,
+    pub(crate) optimization: ImageOptimizationReport,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(TS))]
 pub(crate) struct WasmEarsEmissiveData {
     pub(crate) enabled: bool,
     pub(crate) palette: Vec<u32>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(TS))]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct WasmEarsFeatures {
     pub(crate) ears: WasmEarsSettings,
     pub(crate) protrusions: Vec<WasmProtrusion>,
     pub(crate) protrusions_source: WasmTextureSource,
     pub(crate) tail: WasmTailSettings,
+    #[cfg_attr(test, ts(optional))]
     pub(crate) snout: Option<WasmSnoutSettings>,
     pub(crate) wings: WasmWingSettings,
+    #[cfg_attr(test, ts(optional, type = "Uint8Array"))]
     pub(crate) cape: Option<ByteBuf>,
     pub(crate) chest_size: f32,
+    #[cfg_attr(test, ts(optional))]
     pub(crate) alfalfa: Option<WasmAlfalfaData>,
     pub(crate) emissives: WasmEarsEmissiveData,
     pub(crate) data_version: u8,
     pub(crate) apply_template: bool,
 }
 
+/// A named set of features that can be applied to a skin with [`crate::apply_preset`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(TS))]
+pub(crate) struct WasmFeaturePreset {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) description: String,
+    /// Whether the preset ships with this crate rather than being registered from JavaScript.
+    #[serde(default)]
+    pub(crate) builtin: bool,
+    pub(crate) features: WasmEarsFeatures,
+}
+
+/// Options for [`crate::apply_preset`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct WasmApplyPresetOptions {
+    #[serde(flatten)]
+    pub(crate) write: SkinWriteOptions,
+    /// Paints the template texture for the parts the preset adds.
+    pub(crate) apply_template: bool,
+}
+
 fn rbg_to_hex(image::Rgb([r, g, b]): image::Rgb<u8>) -> u32 {
     u32::from_be_bytes([0xFF, r, g, b])
 }
@@ -0,0 +1,487 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, ImageResult, Rgba, RgbaImage};
+use serde_bytes::ByteBuf;
+
+use crate::{
+    model::{
+        WasmEarsAnchor, WasmEarsEmissiveData, WasmEarsFeatures, WasmEarsMode, WasmEarsSettings,
+        WasmFeatureIssue, WasmFeaturePreset, WasmProtrusion, WasmSnoutSettings, WasmTailMode,
+        WasmTailSettings, WasmTextureSource, WasmWingSettings, WasmWingsAnimations, WasmWingsMode,
+    },
+    validation,
+};
+
+/// Size of the wings texture Ears expects in alfalfa.
+const WINGS_TEXTURE_SIZE: (u32, u32) = (20, 16);
+
+const PLACEHOLDER_WINGS_COLOR: Rgba<u8> = Rgba([0x80, 0x80, 0x80, 0xFF]);
+
+fn ears(mode: WasmEarsMode, anchor: WasmEarsAnchor) -> WasmEarsSettings {
+    WasmEarsSettings {
+        mode,
+        anchor,
+        source: WasmTextureSource::SampleSkin,
+    }
+}
+
+fn tail(mode: WasmTailMode, bends: &[f32]) -> WasmTailSettings {
+    let mut all_bends = [0.0; 4];
+    all_bends[..bends.len()].copy_from_slice(bends);
+
+    WasmTailSettings {
+        mode,
+        segments: bends.len() as u8,
+        bends: all_bends,
+        source: WasmTextureSource::SampleSkin,
+    }
+}
+
+fn snout(width: u8, height: u8, length: u8, offset: u8) -> Option<WasmSnoutSettings> {
+    Some(WasmSnoutSettings {
+        width,
+        height,
+        length,
+        offset,
+        source: WasmTextureSource::SampleSkin,
+    })
+}
+
+fn wings(mode: WasmWingsMode) -> WasmWingSettings {
+    WasmWingSettings {
+        mode,
+        animations: if mode == WasmWingsMode::None {
+            WasmWingsAnimations::None
+        } else {
+            WasmWingsAnimations::Normal
+        },
+        wings: None,
+        source: WasmTextureSource::SampleSkin,
+    }
+}
+
+fn builtin(
+    name: &str,
+    description: &str,
+    ears: WasmEarsSettings,
+    protrusions: &[WasmProtrusion],
+    tail: WasmTailSettings,
+    snout: Option<WasmSnoutSettings>,
+    wings: WasmWingSettings,
+) -> WasmFeaturePreset {
+    WasmFeaturePreset {
+        name: name.to_owned(),
+        description: description.to_owned(),
+        builtin: true,
+        features: WasmEarsFeatures {
+            ears,
+            protrusions: protrusions.to_vec(),
+            protrusions_source: WasmTextureSource::SampleSkin,
+            tail,
+            snout,
+            wings,
+            cape: None,
+            chest_size: 0.0,
+            alfalfa: None,
+            emissives: WasmEarsEmissiveData {
+                enabled: false,
+                palette: Vec::new(),
+            },
+            data_version: 1,
+            apply_template: false,
+        },
+    }
+}
+
+/// The presets that ship with this crate.
+pub(crate) fn builtin_presets() -> Vec<WasmFeaturePreset> {
+    use WasmEarsAnchor as Anchor;
+    use WasmEarsMode as Ears;
+    use WasmTailMode as Tail;
+
+    vec![
+        builtin(
+            "fox",
+            "Pointy ears, a long bushy tail and a narrow snout.",
+            ears(Ears::Above, Anchor::Center),
+            &[],
+            tail(Tail::Down, &[10.0, 20.0, 15.0]),
+            snout(4, 2, 3, 5),
+            wings(WasmWingsMode::None),
+        ),
+        builtin(
+            "wolf",
+            "Upright ears, a tail held back and a broad snout.",
+            ears(Ears::Above, Anchor::Center),
+            &[],
+            tail(Tail::Back, &[-10.0, -20.0]),
+            snout(4, 3, 4, 4),
+            wings(WasmWingsMode::None),
+        ),
+        builtin(
+            "cat",
+            "Small ears, claws and a long curling tail.",
+            ears(Ears::Above, Anchor::Front),
+            &[WasmProtrusion::Claws],
+            tail(Tail::Down, &[0.0, -20.0, -30.0, -20.0]),
+            None,
+            wings(WasmWingsMode::None),
+        ),
+        builtin(
+            "dragon",
+            "Horns, claws, a long snout, a heavy tail and a pair of wings.",
+            ears(Ears::None, Anchor::Center),
+            &[WasmProtrusion::Horns, WasmProtrusion::Claws],
+            tail(Tail::Down, &[20.0, 10.0, 10.0, 5.0]),
+            snout(4, 2, 5, 5),
+            wings(WasmWingsMode::SymmetricDual),
+        ),
+        builtin(
+            "bunny",
+            "Tall ears and a short tail.",
+            ears(Ears::Tall, Anchor::Center),
+            &[],
+            tail(Tail::Back, &[0.0]),
+            None,
+            wings(WasmWingsMode::None),
+        ),
+        builtin(
+            "bird",
+            "A short beak, a fanned tail and a pair of wings.",
+            ears(Ears::None, Anchor::Center),
+            &[],
+            tail(Tail::Back, &[-30.0]),
+            snout(2, 1, 2, 4),
+            wings(WasmWingsMode::SymmetricDual),
+        ),
+    ]
+}
+
+/// The built-in presets together with the ones registered from JavaScript.
+#[derive(Debug, Default)]
+pub(crate) struct PresetCatalogue {
+    custom: Vec<WasmFeaturePreset>,
+}
+
+impl PresetCatalogue {
+    /// Every preset, with custom presets hiding built-in ones of the same name.
+    pub(crate) fn list(&self) -> Vec<WasmFeaturePreset> {
+        let mut presets: Vec<_> = builtin_presets()
+            .into_iter()
+            .filter(|preset| self.find_custom(&preset.name).is_none())
+            .collect();
+
+        presets.extend(self.custom.iter().cloned());
+
+        presets
+    }
+
+    fn find_custom(&self, name: &str) -> Option<&WasmFeaturePreset> {
+        self.custom
+            .iter()
+            .find(|preset| preset.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Looks a preset up by name, ignoring case.
+    pub(crate) fn find(&self, name: &str) -> Option<WasmFeaturePreset> {
+        self.find_custom(name).cloned().or_else(|| {
+            builtin_presets()
+                .into_iter()
+                .find(|preset| preset.name.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// Registers the presets in `json`, an array of `{ name, description, features }` objects,
+    /// replacing custom presets of the same name, and returns how many names were registered.
+    /// When a name appears more than once, the last preset with it wins. Nothing is registered if
+    /// any preset is invalid.
+    ///
+    /// Errors are plain messages, which [`crate::register_presets`] turns into a `JsError`.
+    pub(crate) fn register_json(&mut self, json: &str) -> Result<usize, String> {
+        let presets: Vec<WasmFeaturePreset> =
+            serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        for preset in &presets {
+            if preset.name.trim().is_empty() {
+                return Err("Presets need a name".to_owned());
+            }
+
+            let issues = preset_issues(&preset.features).map_err(|err| err.to_string())?;
+            if !issues.is_empty() {
+                let messages: Vec<_> = issues.into_iter().map(|issue| issue.message).collect();
+
+                return Err(format!(
+                    "Invalid preset {}: {}",
+                    preset.name,
+                    messages.join("; ")
+                ));
+            }
+        }
+
+        let mut names: Vec<_> = presets
+            .iter()
+            .map(|preset| preset.name.to_ascii_lowercase())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+
+        for mut preset in presets {
+            preset.builtin = false;
+
+            self.custom
+                .retain(|existing| !existing.name.eq_ignore_ascii_case(&preset.name));
+            self.custom.push(preset);
+        }
+
+        Ok(names.len())
+    }
+
+    pub(crate) fn clear_custom(&mut self) {
+        self.custom.clear();
+    }
+}
+
+/// A plain wings texture, used when a preset adds wings to a skin that doesn't have a wings
+/// texture, as Ears doesn't render wings without one.
+fn placeholder_wings() -> ImageResult<ByteBuf> {
+    let (width, height) = WINGS_TEXTURE_SIZE;
+    let texture = RgbaImage::from_pixel(width, height, PLACEHOLDER_WINGS_COLOR);
+
+    let mut bytes = Vec::new();
+    texture.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+
+    Ok(ByteBuf::from(bytes))
+}
+
+/// Puts `preset` on top of the features a skin already has. The preset decides the shape of every
+/// part, while textures, alfalfa and the emissive palette of the skin are kept.
+pub(crate) fn apply_preset_features(
+    preset: &WasmEarsFeatures,
+    existing: Option<WasmEarsFeatures>,
+) -> ImageResult<WasmEarsFeatures> {
+    let mut features = preset.clone();
+
+    if let Some(existing) = existing {
+        features.cape = existing.cape;
+        features.alfalfa = existing.alfalfa;
+        features.emissives = existing.emissives;
+
+        if features.wings.wings.is_none() {
+            features.wings.wings = existing.wings.wings;
+        }
+    }
+
+    if features.wings.mode != WasmWingsMode::None && features.wings.wings.is_none() {
+        features.wings.wings = Some(placeholder_wings()?);
+    }
+
+    Ok(features)
+}
+
+/// Checks `preset` the way it would be written to a skin without features, as presets get
+/// placeholder wings when they don't come with a wings texture.
+fn preset_issues(preset: &WasmEarsFeatures) -> ImageResult<Vec<WasmFeatureIssue>> {
+    let features = apply_preset_features(preset, None)?;
+
+    Ok(validation::validate_features(&features))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::model::WasmAlfalfaData;
+
+    use super::*;
+
+    fn preset(name: &str, description: &str) -> WasmFeaturePreset {
+        WasmFeaturePreset {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            builtin: false,
+            ..builtin_presets().remove(0)
+        }
+    }
+
+    fn json(presets: &[WasmFeaturePreset]) -> String {
+        serde_json::to_string(presets).unwrap()
+    }
+
+    fn names(catalogue: &PresetCatalogue) -> Vec<String> {
+        catalogue
+            .list()
+            .into_iter()
+            .map(|preset| preset.name)
+            .collect()
+    }
+
+    #[test]
+    fn builtin_presets_are_valid() {
+        for preset in builtin_presets() {
+            assert_eq!(
+                preset_issues(&preset.features).unwrap(),
+                vec![],
+                "preset {}",
+                preset.name
+            );
+        }
+    }
+
+    #[test]
+    fn presets_are_found_ignoring_case() {
+        let catalogue = PresetCatalogue::default();
+
+        let dragon = catalogue.find("DrAgOn").unwrap();
+        assert_eq!(dragon.name, "dragon");
+        assert!(dragon.builtin);
+
+        assert_eq!(catalogue.find("unicorn"), None);
+    }
+
+    #[test]
+    fn custom_presets_are_registered() {
+        let mut catalogue = PresetCatalogue::default();
+        let otters = [preset("otter", "Round ears")];
+
+        assert_eq!(catalogue.register_json(&json(&otters)), Ok(1));
+        assert_eq!(catalogue.find("Otter").as_ref(), otters.first());
+        assert_eq!(names(&catalogue).last().unwrap(), "otter");
+    }
+
+    #[test]
+    fn nothing_is_registered_when_a_preset_is_invalid() {
+        let mut catalogue = PresetCatalogue::default();
+
+        let mut broken = preset("broken", "");
+        broken.features.tail.segments = 9;
+
+        let error = catalogue
+            .register_json(&json(&[preset("otter", ""), broken]))
+            .unwrap_err();
+        assert!(error.starts_with("Invalid preset broken"), "{error}");
+
+        let error = catalogue
+            .register_json(&json(&[preset("otter", ""), preset(" ", "")]))
+            .unwrap_err();
+        assert_eq!(error, "Presets need a name");
+
+        assert!(catalogue.register_json("[{}]").is_err());
+
+        assert_eq!(catalogue.find("otter"), None);
+        assert_eq!(catalogue.list(), builtin_presets());
+    }
+
+    #[test]
+    fn presets_of_the_same_name_are_replaced_ignoring_case() {
+        let mut catalogue = PresetCatalogue::default();
+
+        catalogue
+            .register_json(&json(&[preset("Otter", "first")]))
+            .unwrap();
+        catalogue
+            .register_json(&json(&[preset("OTTER", "second")]))
+            .unwrap();
+
+        let otters: Vec<_> = catalogue
+            .list()
+            .into_iter()
+            .filter(|preset| preset.name.eq_ignore_ascii_case("otter"))
+            .collect();
+        assert_eq!(otters, vec![preset("OTTER", "second")]);
+    }
+
+    #[test]
+    fn names_repeated_in_one_batch_are_counted_once() {
+        let mut catalogue = PresetCatalogue::default();
+
+        let batch = [
+            preset("otter", "first"),
+            preset("seal", ""),
+            preset("Otter", "second"),
+        ];
+
+        assert_eq!(catalogue.register_json(&json(&batch)), Ok(2));
+        assert_eq!(catalogue.find("otter").unwrap().description, "second");
+        assert_eq!(catalogue.list().len(), builtin_presets().len() + 2);
+    }
+
+    #[test]
+    fn custom_presets_hide_builtin_ones() {
+        let mut catalogue = PresetCatalogue::default();
+
+        catalogue
+            .register_json(&json(&[preset("FOX", "custom")]))
+            .unwrap();
+
+        let foxes: Vec<_> = catalogue
+            .list()
+            .into_iter()
+            .filter(|preset| preset.name.eq_ignore_ascii_case("fox"))
+            .collect();
+        assert_eq!(foxes, vec![preset("FOX", "custom")]);
+        assert!(!catalogue.find("fox").unwrap().builtin);
+
+        catalogue.clear_custom();
+
+        assert!(catalogue.find("fox").unwrap().builtin);
+        assert_eq!(catalogue.list(), builtin_presets());
+    }
+
+    fn dragon() -> WasmEarsFeatures {
+        PresetCatalogue::default().find("dragon").unwrap().features
+    }
+
+    fn fox() -> WasmEarsFeatures {
+        PresetCatalogue::default().find("fox").unwrap().features
+    }
+
+    fn existing_features() -> WasmEarsFeatures {
+        let mut features = fox();
+        features.cape = Some(ByteBuf::from(vec![1, 2, 3]));
+        features.alfalfa = Some(WasmAlfalfaData {
+            version: 1,
+            data: HashMap::from([("custom".to_owned(), ByteBuf::from(vec![4, 5]))]),
+        });
+        features.emissives = WasmEarsEmissiveData {
+            enabled: true,
+            palette: vec![0xFFFF0000],
+        };
+        features.wings.wings = Some(ByteBuf::from(vec![6, 7, 8]));
+
+        features
+    }
+
+    #[test]
+    fn presets_keep_the_textures_and_data_of_the_skin() {
+        let existing = existing_features();
+        let features = apply_preset_features(&dragon(), Some(existing.clone())).unwrap();
+
+        assert_eq!(features.cape, existing.cape);
+        assert_eq!(features.alfalfa, existing.alfalfa);
+        assert_eq!(features.emissives, existing.emissives);
+        assert_eq!(features.wings.wings, existing.wings.wings);
+
+        assert_eq!(features.ears, dragon().ears);
+        assert_eq!(features.tail, dragon().tail);
+        assert_eq!(features.wings.mode, dragon().wings.mode);
+    }
+
+    #[test]
+    fn placeholder_wings_are_only_added_without_a_texture() {
+        let features = apply_preset_features(&dragon(), None).unwrap();
+        let wings = image::load_from_memory(&features.wings.wings.unwrap()).unwrap();
+        assert_eq!((wings.width(), wings.height()), WINGS_TEXTURE_SIZE);
+
+        let mut existing = existing_features();
+        existing.wings.wings = None;
+        let features = apply_preset_features(&dragon(), Some(existing)).unwrap();
+        assert!(features.wings.wings.is_some());
+
+        let features = apply_preset_features(&dragon(), Some(existing_features())).unwrap();
+        assert_eq!(features.wings.wings, existing_features().wings.wings);
+
+        // Presets without wings don't need a texture.
+        let features = apply_preset_features(&fox(), None).unwrap();
+        assert_eq!(features.wings.wings, None);
+    }
+}
@@ -0,0 +1,97 @@
+//! TypeScript declarations for [`WasmEarsFeatures`](crate::model::WasmEarsFeatures), the types it
+//! is made of and [`WasmFeaturePreset`](crate::model::WasmFeaturePreset), shipped in the `.d.ts`
+//! of the wasm package.
+//!
+//! The declarations are generated from the model types with `ts-rs` and checked in as
+//! `bindings/model.d.ts`. The test below fails whenever they no longer match the Rust types; run it
+//! with `UPDATE_BINDINGS=1` to regenerate the file.
+
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const MODEL_DECLARATIONS: &str = include_str!("../bindings/model.d.ts");
+
+#[cfg(test)]
+mod tests {
+    use ts_rs::TS;
+
+    use crate::model::*;
+
+    const BINDINGS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/bindings/model.d.ts");
+
+    /// Turns `enum Name { "A", "B" }` into an exported `type Name = 0 | 1;`. Nothing defines the enums at
+    /// runtime, and `const enum` declarations can't be used with `isolatedModules`, so their values
+    /// are declared as numbers with the variant names in a doc comment.
+    fn numeric_union(declaration: &str) -> Option<String> {
+        let (name, variants) = declaration.strip_prefix("enum ")?.split_once(" { ")?;
+        let variants: Vec<_> = variants
+            .strip_suffix(" }")?
+            .split(", ")
+            .map(|variant| variant.trim_matches('"'))
+            .collect();
+
+        let names: Vec<_> = (0..)
+            .zip(&variants)
+            .map(|(value, variant)| format!("{value} = {variant}"))
+            .collect();
+        let values: Vec<_> = (0..variants.len()).map(|value| value.to_string()).collect();
+
+        Some(format!(
+            "/** {} */\nexport type {name} = {};",
+            names.join(", "),
+            values.join(" | ")
+        ))
+    }
+
+    fn model_declarations() -> String {
+        let declarations = [
+            WasmEarsFeatures::decl(),
+            WasmEarsSettings::decl(),
+            WasmEarsMode::decl(),
+            WasmEarsAnchor::decl(),
+            WasmProtrusion::decl(),
+            WasmTailSettings::decl(),
+            WasmTailMode::decl(),
+            WasmSnoutSettings::decl(),
+            WasmWingSettings::decl(),
+            WasmWingsMode::decl(),
+            WasmWingsAnimations::decl(),
+            WasmTextureSource::decl(),
+            WasmAlfalfaData::decl(),
+            WasmEarsEmissiveData::decl(),
+            WasmFeaturePreset::decl(),
+        ];
+
+        let mut output = String::from(
+            "// Generated from src/model.rs by `UPDATE_BINDINGS=1 cargo test`. Do not edit by hand.\n",
+        );
+
+        for declaration in declarations {
+            let declaration =
+                numeric_union(&declaration).unwrap_or_else(|| format!("export {declaration}"));
+
+            output.push('\n');
+            output.push_str(&declaration);
+            output.push('\n');
+        }
+
+        output
+    }
+
+    #[test]
+    fn model_declarations_are_up_to_date() {
+        let expected = model_declarations();
+
+        if std::env::var_os("UPDATE_BINDINGS").is_some() {
+            std::fs::write(BINDINGS_PATH, &expected).unwrap();
+            return;
+        }
+
+        let actual = std::fs::read_to_string(BINDINGS_PATH).unwrap_or_default();
+
+        assert!(
+            actual == expected,
+            "bindings/model.d.ts is out of date, run the tests with UPDATE_BINDINGS=1 to regenerate it"
+        );
+    }
+}
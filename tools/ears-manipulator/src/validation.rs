@@ -0,0 +1,244 @@
+use std::fmt::Display;
+
+use crate::model::{WasmEarsFeatures, WasmFeatureIssue, WasmTailMode, WasmWingsMode};
+
+/// Number of tail segments Ears can encode.
+const TAIL_SEGMENTS: (u8, u8) = (1, 4);
+
+/// Tail bend angles, in degrees.
+const TAIL_BEND: (f32, f32) = (-90.0, 90.0);
+
+const SNOUT_WIDTH: (u8, u8) = (1, 7);
+const SNOUT_HEIGHT: (u8, u8) = (1, 4);
+const SNOUT_LENGTH: (u8, u8) = (1, 8);
+
+/// Height of the head's front face, which the snout has to fit on.
+const HEAD_HEIGHT: u8 = 8;
+
+const CHEST_SIZE: (f32, f32) = (0.0, 1.0);
+
+/// Size of the emissive palette area.
+const MAX_EMISSIVE_COLORS: usize = 16;
+
+pub(crate) const DATA_VERSIONS: (u8, u8) = (0, 1);
+
+fn check_range<T>(issues: &mut Vec<WasmFeatureIssue>, field: String, value: T, (min, max): (T, T))
+where
+    T: PartialOrd + Into<f64> + Copy + Display,
+{
+    // Written so that NaN fails the check as well.
+    if !(value >= min && value <= max) {
+        issues.push(WasmFeatureIssue {
+            message: format!("{field} must be between {min} and {max}, got {value}"),
+            field,
+            min: Some(min.into()),
+            max: Some(max.into()),
+        });
+    }
+}
+
+/// Checks every field of `features` against what Ears can encode, returning all problems found.
+/// Field names are JSON paths into the serialized features, such as `tail.bends[2]`.
+pub(crate) fn validate_features(features: &WasmEarsFeatures) -> Vec<WasmFeatureIssue> {
+    let mut issues = Vec::new();
+
+    if features.tail.mode != WasmTailMode::None {
+        check_range(
+            &mut issues,
+            "tail.segments".into(),
+            features.tail.segments,
+            TAIL_SEGMENTS,
+        );
+
+        for (index, bend) in features.tail.bends.iter().enumerate() {
+            check_range(
+                &mut issues,
+                format!("tail.bends[{index}]"),
+                *bend,
+                TAIL_BEND,
+            );
+        }
+    }
+
+    if let Some(snout) = &features.snout {
+        check_range(&mut issues, "snout.width".into(), snout.width, SNOUT_WIDTH);
+        check_range(
+            &mut issues,
+            "snout.height".into(),
+            snout.height,
+            SNOUT_HEIGHT,
+        );
+        check_range(
+            &mut issues,
+            "snout.length".into(),
+            snout.length,
+            SNOUT_LENGTH,
+        );
+
+        let max_offset = HEAD_HEIGHT.saturating_sub(snout.height);
+        check_range(
+            &mut issues,
+            "snout.offset".into(),
+            snout.offset,
+            (0, max_offset),
+        );
+    }
+
+    // Wings without a texture are written as if they were turned off.
+    if features.wings.mode != WasmWingsMode::None && features.wings.wings.is_none() {
+        issues.push(WasmFeatureIssue {
+            field: "wings.wings".into(),
+            message: "wings.wings must be set when wings.mode isn't None".into(),
+            min: None,
+            max: None,
+        });
+    }
+
+    check_range(
+        &mut issues,
+        "chestSize".into(),
+        features.chest_size,
+        CHEST_SIZE,
+    );
+
+    if features.emissives.palette.len() > MAX_EMISSIVE_COLORS {
+        issues.push(WasmFeatureIssue {
+            field: "emissives.palette".into(),
+            message: format!(
+                "emissives.palette can have at most {MAX_EMISSIVE_COLORS} colours, got {}",
+                features.emissives.palette.len()
+            ),
+            min: None,
+            max: Some(MAX_EMISSIVE_COLORS as f64),
+        });
+    }
+
+    check_range(
+        &mut issues,
+        "dataVersion".into(),
+        features.data_version,
+        DATA_VERSIONS,
+    );
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use ears_rs::features::{
+        data::tail::{TailData, TailMode},
+        EarsFeatures,
+    };
+    use serde_bytes::ByteBuf;
+
+    use super::*;
+    use crate::model::{WasmSnoutSettings, WasmTextureSource};
+
+    fn valid_features() -> WasmEarsFeatures {
+        WasmEarsFeatures::from(EarsFeatures {
+            tail: Some(TailData {
+                mode: TailMode::Down,
+                segments: 2,
+                bends: [10.0, -20.0, 0.0, 0.0],
+            }),
+            data_version: 1,
+            ..Default::default()
+        })
+    }
+
+    fn invalid_fields(features: &WasmEarsFeatures) -> Vec<String> {
+        validate_features(features)
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect()
+    }
+
+    #[test]
+    fn valid_features_have_no_issues() {
+        assert_eq!(validate_features(&valid_features()), vec![]);
+    }
+
+    #[test]
+    fn tail_segments_are_checked() {
+        let mut features = valid_features();
+
+        for segments in [0, 5] {
+            features.tail.segments = segments;
+            assert_eq!(invalid_fields(&features), vec!["tail.segments"]);
+        }
+
+        // Segments don't matter without a tail.
+        features.tail.mode = WasmTailMode::None;
+        assert_eq!(invalid_fields(&features), Vec::<String>::new());
+    }
+
+    #[test]
+    fn tail_bends_are_checked() {
+        let mut features = valid_features();
+        features.tail.bends[1] = -90.5;
+        features.tail.bends[3] = f32::NAN;
+
+        assert_eq!(
+            invalid_fields(&features),
+            vec!["tail.bends[1]", "tail.bends[3]"]
+        );
+
+        let issue = &validate_features(&features)[0];
+        assert_eq!((issue.min, issue.max), (Some(-90.0), Some(90.0)));
+    }
+
+    #[test]
+    fn snouts_have_to_fit_on_the_head() {
+        let mut features = valid_features();
+        let mut snout = WasmSnoutSettings {
+            width: 7,
+            height: 4,
+            length: 8,
+            offset: 4,
+            source: WasmTextureSource::SampleSkin,
+        };
+
+        features.snout = Some(snout);
+        assert_eq!(invalid_fields(&features), Vec::<String>::new());
+
+        snout.offset = 5;
+        features.snout = Some(snout);
+        assert_eq!(invalid_fields(&features), vec!["snout.offset"]);
+
+        snout = WasmSnoutSettings {
+            width: 8,
+            height: 0,
+            length: 9,
+            ..snout
+        };
+        features.snout = Some(snout);
+        assert_eq!(
+            invalid_fields(&features),
+            vec!["snout.width", "snout.height", "snout.length"]
+        );
+    }
+
+    #[test]
+    fn wings_need_a_texture() {
+        let mut features = valid_features();
+        features.wings.mode = WasmWingsMode::SymmetricDual;
+
+        assert_eq!(invalid_fields(&features), vec!["wings.wings"]);
+
+        features.wings.wings = Some(ByteBuf::from(vec![0x89, b'P', b'N', b'G']));
+        assert_eq!(invalid_fields(&features), Vec::<String>::new());
+    }
+
+    #[test]
+    fn chest_size_is_checked() {
+        let mut features = valid_features();
+
+        for chest_size in [-0.1, 1.1, f32::NAN] {
+            features.chest_size = chest_size;
+            assert_eq!(invalid_fields(&features), vec!["chestSize"]);
+        }
+
+        features.chest_size = 1.0;
+        assert_eq!(invalid_fields(&features), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,222 @@
+use ears_rs::{
+    alfalfa::{self, AlfalfaData},
+    features::EarsFeatures,
+    parser::EarsParser,
+    utils::{self, errors::Result, EarsEmissivePalette},
+};
+use image::{Rgba, RgbaImage};
+
+use crate::model::WasmVanillaReport;
+
+/// The corner where Ears keeps its magic pixel and the v0/v1 feature data, as `(x, y, width, height)`.
+pub(crate) const FEATURE_DATA_AREA: (u32, u32, u32, u32) = (0, 32, 4, 4);
+
+/// The corner where Ears keeps the emissive palette.
+const EMISSIVE_PALETTE_AREA: (u32, u32, u32, u32) = (52, 32, 4, 4);
+
+/// Makes `(x, y, width, height)` fully transparent, returning how many pixels weren't already.
+pub(crate) fn clear_area(
+    skin: &mut RgbaImage,
+    (x, y, width, height): (u32, u32, u32, u32),
+) -> usize {
+    let mut cleared = 0;
+
+    for y in y..(y + height).min(skin.height()) {
+        for x in x..(x + width).min(skin.width()) {
+            let pixel = skin.get_pixel_mut(x, y);
+
+            if pixel.0[3] != 0 {
+                cleared += 1;
+            }
+
+            *pixel = Rgba([0, 0, 0, 0]);
+        }
+    }
+
+    cleared
+}
+
+/// The Ears data of a skin, along with every part of it that couldn't be read.
+#[derive(Default)]
+struct EarsData {
+    features: Option<EarsFeatures>,
+    alfalfa: Option<AlfalfaData>,
+    palette: Option<EarsEmissivePalette>,
+    errors: Vec<String>,
+}
+
+impl EarsData {
+    fn is_empty(&self) -> bool {
+        self.features.is_none()
+            && self.alfalfa.is_none()
+            && self.palette.is_none()
+            && self.errors.is_empty()
+    }
+}
+
+fn read_part<T>(errors: &mut Vec<String>, part: &str, result: Result<Option<T>>) -> Option<T> {
+    result.unwrap_or_else(|error| {
+        errors.push(format!("Couldn't read the {part}: {error}"));
+        None
+    })
+}
+
+/// Reads the feature data, alfalfa and emissive palette of `skin`. Ears keeps all of them in the
+/// bottom half of a 64x64 skin, so legacy 64x32 skins never have any.
+fn read_ears_data(skin: &RgbaImage) -> EarsData {
+    let mut data = EarsData::default();
+
+    if skin.height() < 64 {
+        return data;
+    }
+
+    data.features = read_part(&mut data.errors, "feature data", EarsParser::parse(skin));
+    data.alfalfa = read_part(&mut data.errors, "alfalfa", alfalfa::read_alfalfa(skin));
+    data.palette = read_part(
+        &mut data.errors,
+        "emissive palette",
+        utils::extract_emissive_palette(skin),
+    );
+
+    data
+}
+
+/// Removes every trace of Ears from `skin`: the feature data, the alfalfa payload and the emissive
+/// palette. The base layer is made opaque the way vanilla expects it, which also drops the alfalfa
+/// bits stored in it.
+///
+/// Corrupt Ears data is removed all the same, with what couldn't be read listed in the report.
+pub(crate) fn make_vanilla(skin: &mut RgbaImage) -> WasmVanillaReport {
+    let EarsData {
+        features,
+        alfalfa,
+        palette,
+        errors,
+    } = read_ears_data(skin);
+
+    let mut alfalfa_keys: Vec<String> = alfalfa
+        .map(|data| data.get_data_raw().keys().cloned().collect())
+        .unwrap_or_default();
+    alfalfa_keys.sort();
+
+    let cleared_pixels =
+        clear_area(skin, FEATURE_DATA_AREA) + clear_area(skin, EMISSIVE_PALETTE_AREA);
+
+    let before_strip = skin.clone();
+    utils::strip_alpha(skin);
+
+    let stripped_alpha_pixels = before_strip
+        .pixels()
+        .zip(skin.pixels())
+        .filter(|(before, after)| before != after)
+        .count();
+
+    WasmVanillaReport {
+        feature_data_version: features.map(|f| f.data_version),
+        alfalfa_keys,
+        emissive_colors: palette.map_or(0, |p| p.0.len()),
+        read_errors: errors,
+        cleared_pixels,
+        stripped_alpha_pixels,
+        clean: read_ears_data(skin).is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ears_rs::{
+        alfalfa::AlfalfaDataKey,
+        features::data::{snout::SnoutData, tail::TailData, tail::TailMode},
+    };
+    use image::Rgb;
+
+    use super::*;
+    use crate::convert::write_ears_features;
+
+    const SKIN_COLOR: Rgba<u8> = Rgba([0x40, 0x80, 0xC0, 0xFF]);
+
+    fn skin_with_features() -> RgbaImage {
+        let mut skin = RgbaImage::from_pixel(64, 64, SKIN_COLOR);
+
+        let features = EarsFeatures {
+            tail: Some(TailData {
+                mode: TailMode::Down,
+                segments: 1,
+                ..Default::default()
+            }),
+            snout: Some(SnoutData {
+                offset: 2,
+                width: 4,
+                height: 2,
+                depth: 2,
+            }),
+            emissive: true,
+            data_version: 1,
+            ..Default::default()
+        };
+        write_ears_features(&mut skin, &features).unwrap();
+
+        skin
+    }
+
+    fn skin_with_everything() -> RgbaImage {
+        let mut skin = skin_with_features();
+
+        let mut alfalfa = AlfalfaData::new();
+        alfalfa.set_data(AlfalfaDataKey::Erase, vec![0, 0, 8, 8]);
+        alfalfa.set_data(AlfalfaDataKey::Cape, vec![0x89, b'P', b'N', b'G']);
+        alfalfa::write_alfalfa(&alfalfa, &mut skin).unwrap();
+
+        let palette = EarsEmissivePalette(vec![Rgb([0xFF, 0, 0]), Rgb([0, 0xFF, 0])]);
+        utils::write_emissive_palette(&mut skin, &palette).unwrap();
+
+        skin
+    }
+
+    #[test]
+    fn everything_is_removed() {
+        let mut skin = skin_with_everything();
+
+        let report = make_vanilla(&mut skin);
+
+        assert_eq!(report.feature_data_version, Some(1));
+        assert_eq!(report.alfalfa_keys, vec!["cape", "erase"]);
+        assert_eq!(report.read_errors, Vec::<String>::new());
+        assert!(report.clean);
+    }
+
+    #[test]
+    fn legacy_skins_are_accepted() {
+        let mut skin = RgbaImage::from_pixel(64, 32, SKIN_COLOR);
+
+        let report = make_vanilla(&mut skin);
+
+        assert_eq!(report.cleared_pixels, 0);
+        assert!(report.clean);
+        assert_eq!(skin.dimensions(), (64, 32));
+    }
+
+    #[test]
+    fn corrupt_ears_data_is_removed() {
+        let mut skin = skin_with_features();
+
+        // Keep the magic pixel, so Ears still tries to read what follows it.
+        for (x, y) in (0..4).flat_map(|x| (32..36).map(move |y| (x, y))).skip(1) {
+            skin.put_pixel(x, y, Rgba([0xFF, (x * 40) as u8, (y * 7) as u8, 0xFF]));
+        }
+        for (x, y) in (52..56).flat_map(|x| (32..36).map(move |y| (x, y))) {
+            skin.put_pixel(x, y, Rgba([(x * 3) as u8, 0x12, (y * 5) as u8, 0x7F]));
+        }
+
+        let report = make_vanilla(&mut skin);
+
+        for (x, y) in (0..4)
+            .chain(52..56)
+            .flat_map(|x| (32..36).map(move |y| (x, y)))
+        {
+            assert_eq!(skin.get_pixel(x, y).0[3], 0, "pixel at {x}, {y}");
+        }
+        assert_eq!(report.cleared_pixels, 32);
+        assert!(report.clean);
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use ears_rs::alfalfa::{write_alfalfa, AlfalfaData};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+/// Key of the throwaway entry used to measure how much space is left.
+const PROBE_KEY: &str = "probe";
+
+/// Upper bound for any probe, larger than what a 64x64 skin could ever hold.
+const MAX_PROBE_SIZE: usize = 64 * 64 * 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlfalfaEntryUsage {
+    pub key: String,
+    /// Size of the entry's data.
+    pub data_size: usize,
+    /// Space taken by the entry once written, including its key and framing.
+    pub encoded_size: usize,
+}
+
+/// How much alfalfa data a skin can hold, and how much of that some [`AlfalfaData`] takes up.
+///
+/// All sizes are measured by actually writing to a copy of the skin, so they are expressed in
+/// bytes of entry data that could still be added, not in raw pixels.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlfalfaCapacityReport {
+    pub total_capacity: usize,
+    pub used: usize,
+    pub remaining: usize,
+    /// How many bytes would need to be removed for the data to fit. Zero when it fits.
+    pub overflow: usize,
+    pub fits: bool,
+    pub entries: Vec<AlfalfaEntryUsage>,
+}
+
+/// Whether `data` can be written into `skin`, without touching `skin`.
+pub fn alfalfa_fits(skin: &RgbaImage, data: &AlfalfaData) -> bool {
+    write_alfalfa(data, &mut skin.clone()).is_ok()
+}
+
+/// Measures how much of `skin`'s alfalfa capacity `data` would use.
+pub fn alfalfa_capacity(skin: &RgbaImage, data: &AlfalfaData) -> AlfalfaCapacityReport {
+    let (version, raw) = data.clone().into_raw();
+    let empty = AlfalfaData::new_raw(version, HashMap::new());
+
+    let total_capacity = remaining_space(skin, &empty).unwrap_or(0);
+
+    let mut entries: Vec<AlfalfaEntryUsage> = raw
+        .iter()
+        .map(|(key, value)| {
+            let single =
+                AlfalfaData::new_raw(version, HashMap::from([(key.clone(), value.clone())]));
+
+            let encoded_size = match remaining_space(skin, &single) {
+                Some(remaining) => total_capacity.saturating_sub(remaining),
+                None => value.len() + entry_overhead(skin, version, key, total_capacity),
+            };
+
+            AlfalfaEntryUsage {
+                key: key.clone(),
+                data_size: value.len(),
+                encoded_size,
+            }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let (used, remaining) = match remaining_space(skin, data) {
+        Some(remaining) => (total_capacity.saturating_sub(remaining), remaining),
+        None => (entries.iter().map(|e| e.encoded_size).sum(), 0),
+    };
+
+    let fits = alfalfa_fits(skin, data);
+
+    AlfalfaCapacityReport {
+        total_capacity,
+        used,
+        remaining,
+        overflow: overflow(skin, data),
+        fits,
+        entries,
+    }
+}
+
+/// Picks a key for the probe entry that `raw` doesn't use, so that probing never replaces a real
+/// entry. Every candidate is as long as [`PROBE_KEY`], so the probe's framing always costs the same.
+fn probe_key(raw: &HashMap<String, Vec<u8>>) -> String {
+    std::iter::once(PROBE_KEY.to_owned())
+        .chain((0..).map(|n: usize| format!("{n:0width$}", width = PROBE_KEY.len())))
+        .find(|key| !raw.contains_key(key))
+        .expect("there are more candidate keys than entries")
+}
+
+/// Finds the largest probe entry that still fits next to `data`, or `None` if `data` alone doesn't.
+fn remaining_space(skin: &RgbaImage, data: &AlfalfaData) -> Option<usize> {
+    let (version, raw) = data.clone().into_raw();
+    let probe_key = probe_key(&raw);
+
+    let fits_with_probe = |size: usize| {
+        let mut probed = raw.clone();
+        probed.insert(probe_key.clone(), vec![0xA5; size]);
+
+        alfalfa_fits(skin, &AlfalfaData::new_raw(version, probed))
+    };
+
+    if !alfalfa_fits(skin, data) {
+        return None;
+    }
+
+    if !fits_with_probe(0) {
+        return Some(0);
+    }
+
+    let (mut low, mut high) = (0, MAX_PROBE_SIZE);
+    while low < high {
+        let middle = (low + high).div_ceil(2);
+
+        if fits_with_probe(middle) {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+
+    Some(low)
+}
+
+/// Trims `bytes` bytes of entry data from `raw`, taking them from the largest entries first.
+fn trimmed(version: u8, raw: &HashMap<String, Vec<u8>>, mut bytes: usize) -> AlfalfaData {
+    let mut entries: Vec<_> = raw.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    entries.sort_by(|(a_key, a), (b_key, b)| b.len().cmp(&a.len()).then(a_key.cmp(b_key)));
+
+    for (_, value) in entries.iter_mut() {
+        let cut = bytes.min(value.len());
+        value.truncate(value.len() - cut);
+        bytes -= cut;
+    }
+
+    AlfalfaData::new_raw(version, entries.into_iter().collect())
+}
+
+/// Finds how many bytes of entry data have to be removed for `data` to fit, in the same unit as
+/// the probe used by [`remaining_space`]. If even removing all of it isn't enough, because of the
+/// keys alone, every byte of entry data is reported.
+fn overflow(skin: &RgbaImage, data: &AlfalfaData) -> usize {
+    if alfalfa_fits(skin, data) {
+        return 0;
+    }
+
+    let (version, raw) = data.clone().into_raw();
+    let total: usize = raw.values().map(Vec::len).sum();
+
+    let fits_without = |bytes: usize| alfalfa_fits(skin, &trimmed(version, &raw, bytes));
+
+    if !fits_without(total) {
+        return total;
+    }
+
+    let (mut low, mut high) = (1, total);
+    while low < high {
+        let middle = (low + high) / 2;
+
+        if fits_without(middle) {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    low
+}
+
+/// Estimates the space `key` takes on its own, for entries too large to be measured directly.
+fn entry_overhead(skin: &RgbaImage, version: u8, key: &str, total_capacity: usize) -> usize {
+    let single = AlfalfaData::new_raw(version, HashMap::from([(key.to_owned(), vec![0xA5])]));
+
+    remaining_space(skin, &single)
+        .map(|remaining| total_capacity.saturating_sub(remaining).saturating_sub(1))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use ears_rs::alfalfa::AlfalfaDataKey;
+
+    use super::*;
+
+    fn skin() -> RgbaImage {
+        RgbaImage::new(64, 64)
+    }
+
+    fn probe_sized(size: usize) -> AlfalfaData {
+        let version = AlfalfaData::new().into_raw().0;
+
+        // A key as long as the probe's, so that the entry's framing matches what was measured.
+        AlfalfaData::new_raw(
+            version,
+            HashMap::from([("x".repeat(PROBE_KEY.len()), vec![0x5A; size])]),
+        )
+    }
+
+    fn total_capacity() -> usize {
+        alfalfa_capacity(&skin(), &AlfalfaData::new()).total_capacity
+    }
+
+    #[test]
+    fn data_at_capacity_fits() {
+        let report = alfalfa_capacity(&skin(), &probe_sized(total_capacity()));
+
+        assert!(report.fits);
+        assert_eq!(report.overflow, 0);
+        assert_eq!(report.remaining, 0);
+    }
+
+    #[test]
+    fn data_just_over_capacity_overflows_by_one_byte() {
+        let report = alfalfa_capacity(&skin(), &probe_sized(total_capacity() + 1));
+
+        assert!(!report.fits);
+        assert_eq!(report.overflow, 1);
+    }
+
+    #[test]
+    fn data_well_over_capacity_reports_the_excess() {
+        let report = alfalfa_capacity(&skin(), &probe_sized(total_capacity() + 500));
+
+        assert!(!report.fits);
+        assert_eq!(report.overflow, 500);
+    }
+
+    #[test]
+    fn overflow_is_taken_from_the_largest_entries() {
+        let capacity = total_capacity();
+        let mut data = AlfalfaData::new();
+        data.set_data(AlfalfaDataKey::Custom("small"), vec![1; 8]);
+        data.set_data(AlfalfaDataKey::Custom("large"), vec![2; capacity]);
+
+        let report = alfalfa_capacity(&skin(), &data);
+        let (version, raw) = data.into_raw();
+
+        assert!(report.overflow > 0);
+        assert!(alfalfa_fits(
+            &skin(),
+            &trimmed(version, &raw, report.overflow)
+        ));
+        assert!(!alfalfa_fits(
+            &skin(),
+            &trimmed(version, &raw, report.overflow - 1)
+        ));
+    }
+
+    #[test]
+    fn entries_named_like_the_probe_are_measured() {
+        let named = |key: &str| {
+            let version = AlfalfaData::new().into_raw().0;
+            AlfalfaData::new_raw(version, HashMap::from([(key.to_owned(), vec![1; 100])]))
+        };
+
+        let probe = alfalfa_capacity(&skin(), &named(PROBE_KEY));
+        let other = alfalfa_capacity(&skin(), &named("other"));
+
+        assert!(probe.remaining < probe.total_capacity);
+        assert_eq!(
+            (probe.used, probe.remaining, probe.fits),
+            (other.used, other.remaining, other.fits)
+        );
+        assert_eq!(probe.entries[0].encoded_size, other.entries[0].encoded_size);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod alfalfa;
+pub mod optimize;
+pub mod png;
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+
+use ears_rs::alfalfa::{AlfalfaData, AlfalfaDataKey};
+use image::{ColorType, ImageError, ImageResult, RgbaImage};
+use png::{BitDepth, DeflateCompression, Filter};
+use serde::{Deserialize, Serialize};
+
+use crate::png::PngEncodeOptions;
+
+/// Every filter is tried for every candidate encoding, and the smallest result wins.
+const FILTERS: [Filter; 7] = [
+    Filter::NoFilter,
+    Filter::Sub,
+    Filter::Up,
+    Filter::Avg,
+    Filter::Paeth,
+    Filter::Adaptive,
+    Filter::MinEntropy,
+];
+
+/// Options for writing a skin together with its alfalfa data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SkinWriteOptions {
+    /// How the skin itself is encoded, nested the same way as in the inspector's transplant
+    /// options.
+    pub png: PngEncodeOptions,
+    /// Re-encode image entries (such as capes and wings) as small as possible before writing them,
+    /// so that more fits in the skin.
+    pub optimize_images: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizedImage {
+    pub key: String,
+    pub original_size: usize,
+    pub optimized_size: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageOptimizationReport {
+    /// Every image entry that was looked at, sorted by key.
+    pub images: Vec<OptimizedImage>,
+    pub bytes_saved: usize,
+}
+
+impl ImageOptimizationReport {
+    pub fn push(&mut self, key: impl Into<String>, original_size: usize, optimized_size: usize) {
+        self.images.push(OptimizedImage {
+            key: key.into(),
+            original_size,
+            optimized_size,
+        });
+
+        self.bytes_saved += original_size.saturating_sub(optimized_size);
+        self.images.sort_by(|a, b| a.key.cmp(&b.key));
+    }
+}
+
+/// A lossless way of storing the pixels of an image.
+struct Candidate {
+    color: png::ColorType,
+    depth: BitDepth,
+    palette: Option<(Vec<u8>, Vec<u8>)>,
+    data: Vec<u8>,
+}
+
+/// Re-encodes a PNG with the smallest lossless settings found, trying palette, greyscale and RGB
+/// encodings where the image allows them. Metadata chunks are dropped.
+///
+/// Returns the original bytes when nothing smaller was found, or when the image can't be
+/// re-encoded without losing information (e.g. 16-bit images).
+pub fn optimize_png(bytes: &[u8]) -> ImageResult<Vec<u8>> {
+    let image = image::load_from_memory(bytes)?;
+
+    if !matches!(
+        image.color(),
+        ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+    ) {
+        return Ok(bytes.to_vec());
+    }
+
+    let image = image.into_rgba8();
+    let mut smallest = bytes.to_vec();
+
+    for candidate in candidates(&image) {
+        for filter in FILTERS {
+            let encoded = encode_candidate(&image, &candidate, filter)?;
+
+            if encoded.len() < smallest.len() {
+                smallest = encoded;
+            }
+        }
+    }
+
+    Ok(smallest)
+}
+
+/// Re-encodes every entry with [`optimize_png`]. Entries that fail to decode are left untouched,
+/// as they would fail just the same without optimizing.
+pub fn optimize_image_entries<'a>(
+    entries: impl IntoIterator<Item = (&'a str, &'a mut Vec<u8>)>,
+) -> ImageOptimizationReport {
+    let mut report = ImageOptimizationReport::default();
+
+    for (key, data) in entries {
+        let Ok(optimized) = optimize_png(data) else {
+            continue;
+        };
+
+        report.push(key, data.len(), optimized.len());
+        *data = optimized;
+    }
+
+    report
+}
+
+/// Runs [`optimize_image_entries`] on the cape and wings of `data`.
+pub fn optimize_alfalfa_images(data: &mut AlfalfaData) -> ImageOptimizationReport {
+    let image_keys: [&'static str; 2] = [AlfalfaDataKey::Cape.into(), AlfalfaDataKey::Wings.into()];
+
+    let mut images: Vec<(&str, Vec<u8>)> = image_keys
+        .into_iter()
+        .filter_map(|key| Some((key, data.get_data_raw().get(key)?.clone())))
+        .collect();
+
+    let report = optimize_image_entries(images.iter_mut().map(|(key, image)| (*key, image)));
+
+    for (key, image) in images {
+        data.set_data(AlfalfaDataKey::Custom(key), image);
+    }
+
+    report
+}
+
+fn candidates(image: &RgbaImage) -> Vec<Candidate> {
+    let pixels: Vec<[u8; 4]> = image.pixels().map(|p| p.0).collect();
+
+    let opaque = pixels.iter().all(|p| p[3] == 0xFF);
+    let grey = pixels.iter().all(|p| p[0] == p[1] && p[1] == p[2]);
+
+    let mut candidates = vec![Candidate {
+        color: png::ColorType::Rgba,
+        depth: BitDepth::Eight,
+        palette: None,
+        data: pixels.iter().flatten().copied().collect(),
+    }];
+
+    if opaque {
+        candidates.push(Candidate {
+            color: png::ColorType::Rgb,
+            depth: BitDepth::Eight,
+            palette: None,
+            data: pixels.iter().flat_map(|p| [p[0], p[1], p[2]]).collect(),
+        });
+    }
+
+    if grey {
+        candidates.push(if opaque {
+            Candidate {
+                color: png::ColorType::Grayscale,
+                depth: BitDepth::Eight,
+                palette: None,
+                data: pixels.iter().map(|p| p[0]).collect(),
+            }
+        } else {
+            Candidate {
+                color: png::ColorType::GrayscaleAlpha,
+                depth: BitDepth::Eight,
+                palette: None,
+                data: pixels.iter().flat_map(|p| [p[0], p[3]]).collect(),
+            }
+        });
+    }
+
+    if let Some(candidate) = palette_candidate(image, &pixels) {
+        candidates.push(candidate);
+    }
+
+    candidates
+}
+
+/// Builds an indexed encoding with the smallest bit depth the colour count allows, if there are
+/// at most 256 colours.
+fn palette_candidate(image: &RgbaImage, pixels: &[[u8; 4]]) -> Option<Candidate> {
+    let mut colors: Vec<[u8; 4]> = pixels.to_vec();
+    colors.sort_unstable();
+    colors.dedup();
+
+    if colors.len() > 256 {
+        return None;
+    }
+
+    // Translucent colours go first, so that the tRNS chunk can stop at the last of them.
+    colors.sort_by_key(|color| color[3] == 0xFF);
+
+    let indices: HashMap<[u8; 4], u8> = colors
+        .iter()
+        .enumerate()
+        .map(|(index, &color)| (color, index as u8))
+        .collect();
+
+    let depth = match colors.len() {
+        0..=2 => BitDepth::One,
+        3..=4 => BitDepth::Two,
+        5..=16 => BitDepth::Four,
+        _ => BitDepth::Eight,
+    };
+    let bits = depth as usize;
+
+    let row_bytes = (image.width() as usize * bits).div_ceil(8);
+    let mut data = vec![0u8; row_bytes * image.height() as usize];
+
+    for (index, pixel) in pixels.iter().enumerate() {
+        let x = index % image.width() as usize;
+        let y = index / image.width() as usize;
+
+        let bit_offset = x * bits;
+        let shift = 8 - bits - bit_offset % 8;
+        data[y * row_bytes + bit_offset / 8] |= indices[pixel] << shift;
+    }
+
+    let palette = colors.iter().flat_map(|c| [c[0], c[1], c[2]]).collect();
+    let transparency = colors
+        .iter()
+        .take_while(|c| c[3] != 0xFF)
+        .map(|c| c[3])
+        .collect();
+
+    Some(Candidate {
+        color: png::ColorType::Indexed,
+        depth,
+        palette: Some((palette, transparency)),
+        data,
+    })
+}
+
+fn encode_candidate(
+    image: &RgbaImage,
+    candidate: &Candidate,
+    filter: Filter,
+) -> ImageResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, image.width(), image.height());
+        encoder.set_color(candidate.color);
+        encoder.set_depth(candidate.depth);
+        encoder.set_deflate_compression(DeflateCompression::Level(9));
+        encoder.set_filter(filter);
+
+        if let Some((palette, transparency)) = &candidate.palette {
+            encoder.set_palette(palette.as_slice());
+
+            if !transparency.is_empty() {
+                encoder.set_trns(transparency.as_slice());
+            }
+        }
+
+        let mut writer = encoder.write_header().map_err(encoding_error)?;
+        writer
+            .write_image_data(&candidate.data)
+            .map_err(encoding_error)?;
+    }
+
+    Ok(bytes)
+}
+
+fn encoding_error(err: png::EncodingError) -> ImageError {
+    ImageError::Encoding(image::error::EncodingError::new(
+        image::error::ImageFormatHint::Exact(image::ImageFormat::Png),
+        err,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Rgba};
+
+    use super::*;
+
+    /// An image that uses every colour in `colors`, repeating them in order.
+    fn image_with_colors(width: u32, height: u32, colors: &[[u8; 4]]) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            Rgba(colors[(y * width + x) as usize % colors.len()])
+        })
+    }
+
+    fn opaque_colors(count: usize) -> Vec<[u8; 4]> {
+        (0..count)
+            .map(|i| [i as u8, (i * 3) as u8, (i * 7) as u8, 0xFF])
+            .collect()
+    }
+
+    fn pixels(image: &RgbaImage) -> Vec<[u8; 4]> {
+        image.pixels().map(|p| p.0).collect()
+    }
+
+    fn encode(image: &RgbaImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> RgbaImage {
+        image::load_from_memory(bytes).unwrap().into_rgba8()
+    }
+
+    fn header(bytes: &[u8]) -> (png::ColorType, BitDepth, Option<Vec<u8>>) {
+        let reader = png::Decoder::new(Cursor::new(bytes)).read_info().unwrap();
+        let info = reader.info();
+
+        (
+            info.color_type,
+            info.bit_depth,
+            info.trns.as_ref().map(|trns| trns.to_vec()),
+        )
+    }
+
+    /// Encodes `candidate` with every filter and checks that the pixels survive.
+    fn assert_round_trips(image: &RgbaImage, candidate: &Candidate) {
+        for filter in FILTERS {
+            let bytes = encode_candidate(image, candidate, filter).unwrap();
+
+            assert_eq!(&decode(&bytes), image, "{filter:?}");
+            assert_eq!(header(&bytes).0, candidate.color, "{filter:?}");
+            assert_eq!(header(&bytes).1, candidate.depth, "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn palette_bit_depth_follows_the_colour_count() {
+        // 7 pixels per row, so that rows below 8 bits per pixel end partway through a byte.
+        for (count, depth) in [
+            (2, BitDepth::One),
+            (3, BitDepth::Two),
+            (4, BitDepth::Two),
+            (5, BitDepth::Four),
+            (16, BitDepth::Four),
+            (17, BitDepth::Eight),
+        ] {
+            let image = image_with_colors(7, 5, &opaque_colors(count));
+            let candidate = palette_candidate(&image, &pixels(&image)).unwrap();
+
+            assert_eq!(candidate.depth, depth, "{count} colours");
+            assert_round_trips(&image, &candidate);
+        }
+    }
+
+    #[test]
+    fn translucent_colours_are_kept_in_the_trns_chunk() {
+        let mut colors = opaque_colors(3);
+        colors.push([0, 0, 0, 0]);
+        colors.push([0xFF, 0, 0, 0x80]);
+
+        let image = image_with_colors(7, 5, &colors);
+        let candidate = palette_candidate(&image, &pixels(&image)).unwrap();
+        assert_round_trips(&image, &candidate);
+
+        let bytes = encode_candidate(&image, &candidate, Filter::NoFilter).unwrap();
+        assert_eq!(header(&bytes).2, Some(vec![0, 0x80]));
+    }
+
+    #[test]
+    fn opaque_palettes_have_no_trns_chunk() {
+        let image = image_with_colors(7, 5, &opaque_colors(4));
+        let candidate = palette_candidate(&image, &pixels(&image)).unwrap();
+
+        let bytes = encode_candidate(&image, &candidate, Filter::NoFilter).unwrap();
+        assert_eq!(header(&bytes).2, None);
+    }
+
+    #[test]
+    fn images_with_too_many_colours_have_no_palette() {
+        let colors: Vec<_> = (0..300u32)
+            .map(|i| [i as u8, (i >> 8) as u8, 0, 0xFF])
+            .collect();
+        let image = image_with_colors(20, 15, &colors);
+
+        assert!(palette_candidate(&image, &pixels(&image)).is_none());
+    }
+
+    #[test]
+    fn every_candidate_round_trips() {
+        let images = [
+            // Opaque grey
+            RgbaImage::from_fn(20, 16, |x, y| {
+                let v = (x * 12 + y) as u8;
+                Rgba([v, v, v, 0xFF])
+            }),
+            // Translucent grey
+            RgbaImage::from_fn(20, 16, |x, y| {
+                Rgba([x as u8 * 9, x as u8 * 9, x as u8 * 9, y as u8 * 16])
+            }),
+            // Opaque colour with more than 256 colours
+            RgbaImage::from_fn(20, 16, |x, y| {
+                Rgba([x as u8 * 12, y as u8 * 16, 0x40, 0xFF])
+            }),
+            // Translucent colour
+            RgbaImage::from_fn(20, 16, |x, y| Rgba([x as u8, y as u8, 0x40, (x * y) as u8])),
+        ];
+
+        for image in images {
+            for candidate in candidates(&image) {
+                assert_round_trips(&image, &candidate);
+            }
+        }
+    }
+
+    #[test]
+    fn the_smallest_encoding_is_chosen() {
+        let image = image_with_colors(20, 16, &opaque_colors(6));
+        let original = encode(&image);
+
+        let smallest = candidates(&image)
+            .iter()
+            .flat_map(|candidate| {
+                FILTERS.map(|filter| encode_candidate(&image, candidate, filter).unwrap().len())
+            })
+            .min()
+            .unwrap();
+
+        let optimized = optimize_png(&original).unwrap();
+
+        assert_eq!(optimized.len(), smallest.min(original.len()));
+        assert_eq!(decode(&optimized), image);
+    }
+
+    #[test]
+    fn images_that_are_already_small_are_returned_as_is() {
+        let image = image_with_colors(20, 16, &opaque_colors(6));
+        let optimized = optimize_png(&encode(&image)).unwrap();
+
+        assert_eq!(optimize_png(&optimized).unwrap(), optimized);
+    }
+}
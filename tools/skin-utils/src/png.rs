@@ -0,0 +1,275 @@
+use image::{
+    codecs::png::{CompressionType, FilterType, PngEncoder},
+    ImageEncoder, ImageResult, RgbaImage,
+};
+use serde::{Deserialize, Serialize};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Chunks that describe how colours should be interpreted. They are unsafe-to-copy by the spec's
+/// naming rules, but stay valid as long as the pixels keep meaning the same colours.
+const COLOUR_CHUNKS: [&[u8; 4]; 5] = [b"iCCP", b"sRGB", b"gAMA", b"cHRM", b"cICP"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PngCompression {
+    #[default]
+    Fast,
+    Default,
+    Best,
+}
+
+impl From<PngCompression> for CompressionType {
+    fn from(value: PngCompression) -> Self {
+        match value {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Best => CompressionType::Best,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PngFilter {
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    #[default]
+    Adaptive,
+}
+
+impl From<PngFilter> for FilterType {
+    fn from(value: PngFilter) -> Self {
+        match value {
+            PngFilter::None => FilterType::NoFilter,
+            PngFilter::Sub => FilterType::Sub,
+            PngFilter::Up => FilterType::Up,
+            PngFilter::Avg => FilterType::Avg,
+            PngFilter::Paeth => FilterType::Paeth,
+            PngFilter::Adaptive => FilterType::Adaptive,
+        }
+    }
+}
+
+/// How a skin should be written back to PNG. The defaults match what `image` does on its own,
+/// except that metadata from the source file is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PngEncodeOptions {
+    pub compression: PngCompression,
+    pub filter: PngFilter,
+    pub preserve_metadata: bool,
+}
+
+impl Default for PngEncodeOptions {
+    fn default() -> Self {
+        Self {
+            compression: PngCompression::default(),
+            filter: PngFilter::default(),
+            preserve_metadata: true,
+        }
+    }
+}
+
+/// Encodes `image` as an RGBA8 PNG.
+///
+/// When `source` is the PNG the image was decoded from and `options.preserve_metadata` is set,
+/// its ancillary chunks (text, colour profile, physical size, ...) are carried over.
+pub fn encode_png(
+    image: &RgbaImage,
+    source: Option<&[u8]>,
+    options: &PngEncodeOptions,
+) -> ImageResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    {
+        let encoder = PngEncoder::new_with_quality(
+            &mut bytes,
+            options.compression.into(),
+            options.filter.into(),
+        );
+
+        encoder.write_image(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            image::ExtendedColorType::Rgba8,
+        )?;
+    }
+
+    let Some(source) = source.filter(|_| options.preserve_metadata) else {
+        return Ok(bytes);
+    };
+
+    let preserved: Vec<&[u8]> = chunks(source)
+        .filter(|chunk| is_preserved_chunk(chunk.kind))
+        .map(|chunk| chunk.raw)
+        .collect();
+
+    if preserved.is_empty() {
+        return Ok(bytes);
+    }
+
+    let Some(first_idat) = chunks(&bytes).find(|chunk| chunk.kind == b"IDAT") else {
+        return Ok(bytes);
+    };
+
+    let insert_at = first_idat.offset;
+
+    let mut result =
+        Vec::with_capacity(bytes.len() + preserved.iter().map(|c| c.len()).sum::<usize>());
+    result.extend_from_slice(&bytes[..insert_at]);
+    for chunk in preserved {
+        result.extend_from_slice(chunk);
+    }
+    result.extend_from_slice(&bytes[insert_at..]);
+
+    Ok(result)
+}
+
+pub(crate) struct PngChunk<'a> {
+    pub(crate) kind: &'a [u8; 4],
+    /// The whole chunk, including its length, type and CRC.
+    pub(crate) raw: &'a [u8],
+    pub(crate) offset: usize,
+}
+
+/// Iterates the chunks of a PNG file, stopping at the first malformed one.
+pub(crate) fn chunks(png: &[u8]) -> impl Iterator<Item = PngChunk<'_>> {
+    let mut offset = if png.starts_with(PNG_SIGNATURE) {
+        PNG_SIGNATURE.len()
+    } else {
+        png.len()
+    };
+
+    std::iter::from_fn(move || {
+        let header = png.get(offset..offset + 8)?;
+        let length = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+        let kind: &[u8; 4] = header[4..8].try_into().ok()?;
+
+        let end = offset.checked_add(12)?.checked_add(length)?;
+        let raw = png.get(offset..end)?;
+
+        let chunk = PngChunk { kind, raw, offset };
+
+        offset = end;
+        Some(chunk)
+    })
+}
+
+fn is_preserved_chunk(kind: &[u8; 4]) -> bool {
+    let is_critical = kind[0].is_ascii_uppercase();
+    let is_safe_to_copy = kind[3].is_ascii_lowercase();
+
+    !is_critical && (is_safe_to_copy || COLOUR_CHUNKS.contains(&kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+    use png::chunk::ChunkType;
+
+    use super::*;
+
+    fn image() -> RgbaImage {
+        RgbaImage::from_fn(8, 4, |x, y| Rgba([x as u8 * 30, y as u8 * 60, 0x80, 0xFF]))
+    }
+
+    /// A PNG of [`image`] with the given chunks written between the header and the image data.
+    fn source_with(extra: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let image = image();
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, image.width(), image.height());
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+
+            let mut writer = encoder.write_header().unwrap();
+            for (kind, data) in extra {
+                writer.write_chunk(ChunkType(**kind), data).unwrap();
+            }
+            writer.write_image_data(image.as_raw()).unwrap();
+        }
+
+        bytes
+    }
+
+    fn kinds(png: &[u8]) -> Vec<&[u8; 4]> {
+        chunks(png).map(|chunk| chunk.kind).collect()
+    }
+
+    fn ancillary_kinds(png: &[u8]) -> Vec<&[u8; 4]> {
+        kinds(png)
+            .into_iter()
+            .filter(|kind| kind[0].is_ascii_lowercase())
+            .collect()
+    }
+
+    const TEXT: (&[u8; 4], &[u8]) = (b"tEXt", b"Author\0Someone");
+    const PHYSICAL_SIZE: (&[u8; 4], &[u8]) = (b"pHYs", &[0, 0, 0x0B, 0x13, 0, 0, 0x0B, 0x13, 1]);
+    const SRGB: (&[u8; 4], &[u8]) = (b"sRGB", &[0]);
+    const TIME: (&[u8; 4], &[u8]) = (b"tIME", &[0x07, 0xEA, 10, 18, 12, 0, 0]);
+
+    #[test]
+    fn safe_to_copy_and_colour_chunks_are_kept() {
+        let source = source_with(&[TEXT, TIME, SRGB, PHYSICAL_SIZE]);
+
+        let encoded = encode_png(&image(), Some(&source), &PngEncodeOptions::default()).unwrap();
+
+        assert_eq!(ancillary_kinds(&encoded), vec![b"tEXt", b"sRGB", b"pHYs"]);
+        assert_eq!(
+            image::load_from_memory(&encoded).unwrap().into_rgba8(),
+            image()
+        );
+
+        let text = chunks(&encoded)
+            .find(|chunk| chunk.kind == b"tEXt")
+            .unwrap();
+        assert_eq!(&text.raw[8..text.raw.len() - 4], TEXT.1);
+    }
+
+    #[test]
+    fn kept_chunks_come_before_the_image_data() {
+        let source = source_with(&[TEXT]);
+
+        let encoded = encode_png(&image(), Some(&source), &PngEncodeOptions::default()).unwrap();
+        let kinds = kinds(&encoded);
+
+        assert_eq!(kinds.first(), Some(&b"IHDR"));
+        assert_eq!(kinds.last(), Some(&b"IEND"));
+
+        let text = kinds.iter().position(|&kind| kind == b"tEXt").unwrap();
+        let idat = kinds.iter().position(|&kind| kind == b"IDAT").unwrap();
+        assert!(text < idat);
+    }
+
+    #[test]
+    fn metadata_is_dropped_when_not_preserved() {
+        let source = source_with(&[TEXT, SRGB]);
+        let options = PngEncodeOptions {
+            preserve_metadata: false,
+            ..Default::default()
+        };
+
+        let dropped = encode_png(&image(), Some(&source), &options).unwrap();
+        let without_source = encode_png(&image(), None, &PngEncodeOptions::default()).unwrap();
+
+        assert_eq!(ancillary_kinds(&dropped), Vec::<&[u8; 4]>::new());
+        assert_eq!(dropped, without_source);
+    }
+
+    #[test]
+    fn malformed_chunks_end_the_iteration() {
+        let source = source_with(&[TEXT]);
+
+        // Cut off in the middle of the text chunk.
+        let text = chunks(&source).find(|chunk| chunk.kind == b"tEXt").unwrap();
+        let truncated = &source[..text.offset + 10];
+        assert_eq!(kinds(truncated), vec![b"IHDR"]);
+
+        assert_eq!(kinds(b"not a png"), Vec::<&[u8; 4]>::new());
+    }
+}